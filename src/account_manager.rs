@@ -1,801 +1,5830 @@
+use crate::checked_math::add_checked;
+use crate::checked_math::shift_total;
+use crate::checked_math::sub_checked;
+use crate::checked_math::sum_checked;
+use crate::checked_math::total_checked;
+use crate::config::Config;
+use crate::config::DecimalFormat;
+use crate::config::DisputeOverholdPolicy;
+use crate::config::OutputSchema;
+use crate::config::UnknownTypePolicy;
+use crate::fixed_width::FixedLayout;
+use crate::money::Money;
+use crate::process_error::ProcessError;
+use crate::tx_stream_reader::TxStreamReader;
 use crate::ClientAccount;
 use crate::Transaction;
 use crate::TxType;
+use crate::DECIMAL_PRECISION;
+use chrono::DateTime;
+use chrono::Utc;
 use rust_decimal::Decimal;
 use std::collections::hash_map::Entry::Occupied;
 use std::collections::hash_map::Entry::Vacant;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt;
+use std::fs::File;
 use std::io;
+use std::io::BufRead;
+use std::io::Read;
+use std::io::Seek;
+use std::time::Duration;
+use std::time::Instant;
 
+/// A caller's business rule, returning `false` for a transaction which must not be applied.
+/// It is `Send` so the manager can still be shared between threads.
+pub type TxFilter = Box<dyn Fn(&Transaction) -> bool + Send>;
+
+/// Called by bulk ingestion after each row it applies.
+type OnApplied<'a> = dyn FnMut(&AccountManager, &Transaction) -> Result<(), Box<dyn Error>> + 'a;
+
+/// Position of the client column in an input row; rows are mapped onto `Transaction` by position.
+const CLIENT_FIELD: usize = 1;
+/// Position of the amount column in an input row.
+const AMOUNT_FIELD: usize = 3;
+/// Number of leading columns deserialized into a `Transaction`; any later columns are ignored.
+const TRANSACTION_FIELDS: usize = 6;
+
+#[derive(Default)]
 pub struct AccountManager {
     pub accounts: HashMap<u16, ClientAccount>,
     transactions: HashMap<u32, Transaction>,
+    tx_order: VecDeque<u32>,
+    rejections: Vec<Rejection>,
+    chargebacks: Vec<ChargebackRecord>,
+    corrections: Vec<CorrectionRecord>,
+    events: Vec<Transaction>,
+    balance_trace: Vec<BalancePoint>,
+    locked_violations: Vec<Transaction>,
+    held_alerts: Vec<HeldAlert>,
+    /// When each open dispute was raised, for disputes whose row carried a timestamp.
+    dispute_times: HashMap<u32, DateTime<Utc>>,
+    /// Ids of deposits & withdrawals applied by an earlier run, see `process_csv_with_dedup`.
+    seen_tx_ids: HashSet<u32>,
+    tx_filter: Option<TxFilter>,
+    last_run_stats: Option<RunStats>,
+    config: Config,
 }
 
-impl std::fmt::Display for AccountManager {
-    fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
-        self.to_csv().unwrap();
-        Ok(())
+/// Audit trail entry written whenever a chargeback reverses a disputed transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChargebackRecord {
+    pub client: u16,
+    pub tx: u32,
+    pub amount: Decimal,
+    pub locked: bool,
+}
+
+/// Throughput of the last `process_file` run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunStats {
+    pub rows: usize,
+    pub duration: Duration,
+}
+
+impl RunStats {
+    pub fn rows_per_sec(&self) -> f64 {
+        let secs = self.duration.as_secs_f64();
+        if secs > 0.0 {
+            self.rows as f64 / secs
+        } else {
+            0.0
+        }
     }
 }
 
-impl Default for AccountManager {
-    fn default() -> Self {
-        AccountManager {
-            accounts: HashMap::new(),
-            transactions: HashMap::new(),
+/// Aggregate figures across every account, see `AccountManager::stats`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub accounts: usize,
+    /// Accounts locked by a chargeback.
+    pub locked: usize,
+    pub total_available: Decimal,
+    pub total_held: Decimal,
+    /// Transactions currently under dispute, i.e. neither resolved nor charged back.
+    pub disputed_count: usize,
+    pub charged_back_total: Decimal,
+}
+
+/// Figures for one client's stored transactions, see `AccountManager::client_stats`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientStats {
+    pub deposits: usize,
+    /// Withdrawals, including pending ones.
+    pub withdrawals: usize,
+    /// Transactions currently under dispute, i.e. neither resolved nor charged back.
+    pub disputed: usize,
+    /// The sum of every deposit & withdrawal amount.
+    pub volume: Decimal,
+}
+
+/// A client's balances straight after a transaction was applied, see `Config::trace_balances`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BalancePoint {
+    pub tx: u32,
+    pub client: u16,
+    pub available: Decimal,
+    pub held: Decimal,
+}
+
+/// The net change one transaction made to its client's balances, see `process_tx_delta`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BalanceDelta {
+    pub available: Decimal,
+    pub held: Decimal,
+}
+
+/// The accounts as they stood at one point, to report later changes against.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    accounts: HashMap<u16, ClientAccount>,
+}
+
+/// Audit trail entry written when an account's balances are zeroed, holding what they were.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorrectionRecord {
+    pub client: u16,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub pending: Decimal,
+    pub total: Decimal,
+}
+
+/// Raised when a dispute pushes held funds past `Config::held_alert_threshold`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeldAlert {
+    pub client: u16,
+    pub tx: u32,
+    pub held: Decimal,
+}
+
+/// A row which could not be applied during bulk ingestion, keyed by its line in the source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rejection {
+    pub row: u64,
+    pub tx: Option<u32>,
+    pub client: Option<u16>,
+    pub tx_type: Option<TxType>,
+    pub reason: ProcessError,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Row {}: {}", self.row, self.reason)
+    }
+}
+
+/// The fields which differ for one client between two managers, as `(self, other)` pairs.
+/// A client present on only one side is compared against an empty, unlocked account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountDiff {
+    pub client: u16,
+    pub available: Option<(Decimal, Decimal)>,
+    pub held: Option<(Decimal, Decimal)>,
+    pub total: Option<(Decimal, Decimal)>,
+    pub locked: Option<(bool, bool)>,
+}
+
+/// An account written with the column names of `OutputSchema::Short`. `overdrawn` is only
+/// written under `Config::clamp_negative`.
+#[derive(Serialize)]
+struct ShortAccount {
+    client: u16,
+    available: Money,
+    held: Money,
+    total: Money,
+    locked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overdrawn: Option<bool>,
+}
+
+impl ShortAccount {
+    fn new(acc: &ClientAccount, overdrawn: Option<bool>) -> Self {
+        ShortAccount {
+            client: acc.client(),
+            available: acc.available(),
+            held: acc.held(),
+            total: acc.total(),
+            locked: acc.locked() || acc.frozen(),
+            overdrawn,
+        }
+    }
+}
+
+/// An account written with the column names of `OutputSchema::Extended`.
+#[derive(Serialize)]
+struct ExtendedAccount {
+    #[serde(rename = "client_id")]
+    client: u16,
+    #[serde(rename = "available_balance")]
+    available: Money,
+    #[serde(rename = "held_balance")]
+    held: Money,
+    #[serde(rename = "total_balance")]
+    total: Money,
+    #[serde(rename = "is_locked")]
+    locked: bool,
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overdrawn: Option<bool>,
+}
+
+impl ExtendedAccount {
+    fn new(acc: &ClientAccount, overdrawn: Option<bool>) -> Self {
+        ExtendedAccount {
+            client: acc.client(),
+            available: acc.available(),
+            held: acc.held(),
+            total: acc.total(),
+            locked: acc.locked() || acc.frozen(),
+            label: acc.label.clone(),
+            overdrawn,
         }
     }
 }
 
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProcessSummary {
+    pub rows: usize,
+    pub applied: usize,
+    pub rejected: usize,
+    /// Rows passed over because an earlier run already applied them, or because their type is
+    /// unknown under `UnknownTypePolicy::Skip`.
+    pub skipped: usize,
+    /// Rejected disputes which targeted a withdrawal, only counted under `Config::strict_spec`.
+    pub withdrawal_disputes_rejected: usize,
+}
+
+/// Formats the accounts as the CSV `write_csv` produces.
+impl std::fmt::Display for AccountManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut csv = Vec::new();
+        self.write_csv(&mut csv).map_err(|_| fmt::Error)?;
+        f.write_str(&String::from_utf8_lossy(&csv))
+    }
+}
+
 impl AccountManager {
-    fn to_csv(&self) -> Result<(), Box<dyn Error>> {
-        let mut wtr = csv::Writer::from_writer(io::stdout());
-        for acc in self.accounts.values() {
-            wtr.serialize(acc).unwrap();
+    pub fn with_config(config: Config) -> Self {
+        AccountManager {
+            config,
+            ..AccountManager::default()
+        }
+    }
+
+    /// Rebuilds accounts & dispute state by replaying a log captured with `Config::record_events`.
+    pub fn from_event_log(events: &[Transaction]) -> AccountManager {
+        AccountManager::from_event_log_with_config(events, Config::default())
+    }
+
+    /// `from_event_log` for a log captured under `config`, so policies such as the dispute
+    /// overhold policy or dispute window apply as they did originally.
+    pub fn from_event_log_with_config(events: &[Transaction], config: Config) -> AccountManager {
+        let mut acc_man = AccountManager::with_config(Config {
+            record_events: true,
+            ..config
+        });
+        for event in events {
+            // The log only holds transactions which were applied successfully the first time.
+            let _ = acc_man.process_tx(event);
+        }
+        acc_man
+    }
+
+    /// Writes every rejected row as CSV with the columns `row, tx, client, type, reason`.
+    pub fn write_errors_csv<W: io::Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let mut wtr = self.csv_writer(writer);
+        wtr.write_record(["row", "tx", "client", "type", "reason"])?;
+        for rejection in &self.rejections {
+            wtr.write_record(&[
+                rejection.row.to_string(),
+                rejection.tx.map_or_else(String::new, |t| t.to_string()),
+                rejection.client.map_or_else(String::new, |c| c.to_string()),
+                rejection
+                    .tx_type
+                    .as_ref()
+                    .map_or_else(String::new, |t| t.to_string()),
+                rejection.reason.to_string(),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Writes the transaction store, ordered by tx id, as CSV with the columns
+    /// `type, client, tx, amount, is_disputed`. Cancelled pending withdrawals have type `cancel`.
+    pub fn write_transactions_csv<W: io::Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let mut wtr = self.csv_writer(writer);
+        wtr.write_record(["type", "client", "tx", "amount", "is_disputed"])?;
+        let mut transactions: Vec<&Transaction> = self.transactions.values().collect();
+        transactions.sort_by_key(|t| t.tx);
+        for t in transactions {
+            wtr.write_record(&[
+                t.tx_type
+                    .as_ref()
+                    .map_or_else(String::new, |ty| ty.to_string()),
+                t.client.to_string(),
+                t.tx.to_string(),
+                t.amount.map_or_else(String::new, |a| a.to_string()),
+                t.is_disputed.to_string(),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Writes the open disputes, ordered by tx id, as CSV with the columns
+    /// `client, tx, amount, disputed_at`. The amount is the amount held, and `disputed_at` is
+    /// empty unless the dispute row carried a timestamp. Charged back transactions are left out.
+    pub fn write_disputes_csv<W: io::Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let mut wtr = self.csv_writer(writer);
+        wtr.write_record(["client", "tx", "amount", "disputed_at"])?;
+        let mut disputed = self.open_disputes();
+        disputed.sort_by_key(|t| t.tx);
+        for t in disputed {
+            wtr.write_record(&[
+                t.client.to_string(),
+                t.tx.to_string(),
+                t.disputed_amount
+                    .or(t.amount)
+                    .map_or_else(String::new, |a| a.to_string()),
+                self.dispute_times
+                    .get(&t.tx)
+                    .map_or_else(String::new, |at| at.to_rfc3339()),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Disputed transactions which have not yet been resolved or charged back.
+    fn open_disputes(&self) -> Vec<&Transaction> {
+        self.transactions
+            .values()
+            .filter(|t| t.is_disputed && !t.charged_back)
+            .collect()
+    }
+
+    /// Writes how many rows were rejected for each reason as CSV with the columns
+    /// `reason, count`, ordered by reason.
+    pub fn rejection_summary_csv<W: io::Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for rejection in &self.rejections {
+            *counts.entry(rejection.reason.to_string()).or_insert(0) += 1;
+        }
+        let mut wtr = self.csv_writer(writer);
+        wtr.write_record(["reason", "count"])?;
+        for (reason, count) in counts {
+            wtr.write_record(&[reason, count.to_string()])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Encodes the accounts & transactions, including their dispute state, as a compact binary
+    /// checkpoint. The config and the rejection, chargeback & event logs are not included.
+    #[cfg(feature = "binary")]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        crate::binary::encode(&self.accounts, &self.transactions, &self.tx_order)
+    }
+
+    /// Restores a checkpoint written by `to_bytes` into a manager with the default config.
+    #[cfg(feature = "binary")]
+    pub fn from_bytes(b: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let (accounts, transactions, tx_order) = crate::binary::decode(b)?;
+        Ok(AccountManager {
+            accounts,
+            transactions,
+            tx_order,
+            ..AccountManager::default()
+        })
+    }
+
+    /// Builds a manager by processing an in-memory CSV, e.g. a string literal in a test.
+    pub fn from_csv_str(s: &str) -> Result<AccountManager, Box<dyn Error>> {
+        let mut acc_man = AccountManager::default();
+        acc_man.process_csv(s.as_bytes())?;
+        Ok(acc_man)
+    }
+
+    /// Output follows RFC 4180: a field is quoted only when it holds a delimiter, quote or newline.
+    fn csv_writer<W: io::Write>(&self, writer: W) -> csv::Writer<W> {
+        csv::WriterBuilder::new()
+            .delimiter(self.config.delimiter)
+            .quote_style(csv::QuoteStyle::Necessary)
+            .double_quote(true)
+            .from_writer(writer)
+    }
+
+    /// Writes the accounts as CSV, ordered by client id.
+    pub fn write_csv<W: io::Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let mut wtr = self.csv_writer(writer);
+        let mut accounts: Vec<&ClientAccount> = self.accounts.values().collect();
+        accounts.sort_by_key(|acc| acc.client);
+        for acc in accounts {
+            self.write_account(&mut wtr, acc)?;
         }
         wtr.flush()?;
         Ok(())
     }
-    fn process_deposit(&mut self, tx: &Transaction) -> Result<(), Box<dyn Error>> {
+
+    /// Writes one account row as configured, e.g. with a fixed scale or the extended schema.
+    fn write_account<W: io::Write>(
+        &self,
+        wtr: &mut csv::Writer<W>,
+        acc: &ClientAccount,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut acc = acc.clone();
+        let overdrawn = if self.config.clamp_negative {
+            let overdrawn = !acc.is_solvent();
+            acc.available = acc.available.max(Money::default());
+            acc.total = acc.total.max(Money::default());
+            Some(overdrawn)
+        } else {
+            None
+        };
+        let precision = acc
+            .currency
+            .as_ref()
+            .and_then(|c| self.config.currency_precision.get(c));
+        if let Some(&dp) = precision {
+            for balance in [&mut acc.available, &mut acc.held, &mut acc.total] {
+                *balance = balance.round_dp(dp);
+                balance.rescale(dp);
+            }
+        } else if self.config.fixed_scale {
+            acc.available.rescale(DECIMAL_PRECISION);
+            acc.held.rescale(DECIMAL_PRECISION);
+            acc.total.rescale(DECIMAL_PRECISION);
+        }
+        match self.config.output_schema {
+            OutputSchema::Short => wtr.serialize(ShortAccount::new(&acc, overdrawn))?,
+            OutputSchema::Extended => wtr.serialize(ExtendedAccount::new(&acc, overdrawn))?,
+        }
+        Ok(())
+    }
+
+    fn process_deposit(&mut self, tx: &Transaction) -> Result<(), ProcessError> {
         let amount = match tx.amount {
             Some(a) => {
                 if a.lt(&Decimal::new(0, 0)) {
-                    return Err("Cannot Deposit a Negative Amount".into());
+                    return Err(ProcessError::NegativeAmount);
                 } else {
                     a
                 }
             }
-            None => return Err("Amount Required".into()),
+            None => return Err(ProcessError::AmountRequired),
         };
-        match self.transactions.entry(tx.tx) {
-            Occupied(_) => return Err("Duplicate Transaction".into()),
-            Vacant(e) => {
-                e.insert(tx.clone());
-            }
+        if self.config.max_tx_amount.is_some_and(|max| amount > max) {
+            return Err(ProcessError::AmountTooLarge);
         }
+        // Only store the transaction once it has been applied, so a rejection doesn't use up its id.
+        let stored_tx = match self.transactions.entry(tx.tx) {
+            Occupied(_) => return Err(ProcessError::DuplicateTransaction),
+            Vacant(e) => e,
+        };
         match self.accounts.entry(tx.client) {
             Occupied(mut e) => {
                 let account = e.get_mut();
                 if account.locked {
-                    return Err("Account Locked due to Chargeback".into());
+                    return Err(ProcessError::AccountLocked);
                 }
-                account.available += amount;
-                account.total = account.available - account.held;
+                if account.frozen {
+                    return Err(ProcessError::AccountFrozen);
+                }
+                let available = add_checked(account.available, amount)?;
+                account.total = shift_total(
+                    account.total,
+                    amount,
+                    available,
+                    account.held,
+                    account.pending,
+                )?;
+                account.available = available;
             }
             Vacant(e) => {
-                let new_account = ClientAccount {
-                    available: amount,
-                    client: tx.client,
-                    held: Decimal::new(0, 0),
-                    locked: false,
-                    total: amount,
-                };
-                e.insert(new_account);
+                e.insert(ClientAccount::new(
+                    tx.client,
+                    amount,
+                    Decimal::new(0, 0),
+                    false,
+                )?);
             }
         }
+        stored_tx.insert(tx.clone());
+        self.track_stored(tx.tx);
         Ok(())
     }
 
-    fn process_withdraw(&mut self, tx: &Transaction) -> Result<(), Box<dyn Error>> {
+    /// Deposits & withdrawals share one tx id space, so a withdrawal can't reuse a deposit's id.
+    /// Handles both withdrawals and `pending` withdrawals; the latter keep the funds in `pending`
+    /// until a `settle` or `cancel` arrives for the same transaction. A withdrawal for an unknown
+    /// client never opens an overdrawn account: it fails with `NoSuchAccount`, or, without
+    /// `Config::withdrawal_requires_account`, is checked against an empty balance.
+    fn process_withdraw(&mut self, tx: &Transaction) -> Result<(), ProcessError> {
         let amount = match tx.amount {
             Some(a) => {
                 if a.lt(&Decimal::new(0, 0)) {
-                    return Err("Cannot Withdraw a Negative Amount".into());
+                    return Err(ProcessError::NegativeAmount);
                 } else {
                     a
                 }
             }
-            None => return Err("Amount Required".into()),
+            None => return Err(ProcessError::AmountRequired),
         };
-        match self.transactions.entry(tx.tx) {
-            Occupied(_) => return Err("Duplicate Transaction".into()),
-            Vacant(e) => {
-                e.insert(tx.clone());
-            }
+        if self.config.max_tx_amount.is_some_and(|max| amount > max) {
+            return Err(ProcessError::AmountTooLarge);
         }
+        // Only store the transaction once it has been applied, so a rejection doesn't use up its id.
+        let stored_tx = match self.transactions.entry(tx.tx) {
+            Occupied(_) => return Err(ProcessError::DuplicateTransaction),
+            Vacant(e) => e,
+        };
         match self.accounts.entry(tx.client) {
             Occupied(mut e) => {
                 let account = e.get_mut();
                 if account.locked {
-                    return Err("Account Locked due to Chargeback".into());
+                    return Err(ProcessError::AccountLocked);
                 }
-                if (account.available - amount).lt(&Decimal::new(0, 0)) {
-                    return Err("Insufficient Funds".into());
+                if account.frozen {
+                    return Err(ProcessError::AccountFrozen);
                 }
-                account.available -= amount;
-                account.total = account.available - account.held;
+                let available = sub_checked(account.available, amount)?;
+                if available.is_negative() {
+                    return Err(ProcessError::InsufficientFunds);
+                }
+                // A pending withdrawal only moves the funds, so the total is unchanged.
+                let (pending, delta) = if tx.tx_type == Some(TxType::Pending) {
+                    (add_checked(account.pending, amount)?, Decimal::new(0, 0))
+                } else {
+                    (account.pending, -amount)
+                };
+                account.total =
+                    shift_total(account.total, delta, available, account.held, pending)?;
+                account.available = available;
+                account.pending = pending;
+            }
+            Vacant(_) if self.config.withdrawal_requires_account => {
+                return Err(ProcessError::NoSuchAccount)
+            }
+            Vacant(e) => {
+                let zero = Decimal::new(0, 0);
+                if amount > zero {
+                    return Err(ProcessError::InsufficientFunds);
+                }
+                e.insert(ClientAccount::new(tx.client, zero, zero, false)?);
             }
-            Vacant(_) => return Err("Cannot withdraw from a non existent account".into()),
         }
+        stored_tx.insert(tx.clone());
+        self.track_stored(tx.tx);
         Ok(())
     }
 
-    fn process_dispute(&mut self, tx: &Transaction) -> Result<(), Box<dyn Error>> {
-        let mut _account = match self.accounts.entry(tx.client) {
-            Occupied(entry) => {
-                if entry.get().locked {
-                    return Err("Account Locked due to Chargeback".into());
-                }
-                entry
-            }
-            Vacant(_) => {
-                return Err("No Associated Client Account Found".into());
-            }
+    /// Completes a pending withdrawal: `settle` lets the funds leave the account while `cancel`
+    /// returns them to `available`. This is allowed on a locked account, so funds are not stranded.
+    fn process_settlement(&mut self, tx: &Transaction) -> Result<(), ProcessError> {
+        let pending_tx = match self.transactions.get_mut(&tx.tx) {
+            Some(t) => t,
+            None => return Err(ProcessError::TransactionNotFound),
         };
-        match self.transactions.entry(tx.tx) {
-            Occupied(mut e) => {
-                let disputed_tx = e.get_mut();
-                let account = _account.get_mut();
-                if disputed_tx.tx_type.as_ref().unwrap() != &TxType::Deposit {
-                    return Err("Only a Deposit can be disputed".into());
-                }
-                let amount = match disputed_tx.amount {
-                    Some(a) => a,
-                    None => return Err("Amount Required".into()),
-                };
-                account.available -= amount;
-                account.held += amount;
-                disputed_tx.is_disputed = true;
-            }
-            Vacant(_) => {
-                return Err("No Associated Transaction to-be-disputed could be Found".into());
-            }
+        if pending_tx.tx_type != Some(TxType::Pending) {
+            return Err(ProcessError::NotPending);
+        }
+        if pending_tx.client != tx.client {
+            return Err(ProcessError::ClientMismatch);
+        }
+        let account = match self.accounts.get_mut(&tx.client) {
+            Some(a) => a,
+            None => return Err(ProcessError::AccountMissingForTransaction),
+        };
+        let amount = match pending_tx.amount {
+            Some(a) => a,
+            None => return Err(ProcessError::AmountRequired),
         };
+        let pending = sub_checked(account.pending, amount)?;
+        let (available, delta) = if tx.tx_type == Some(TxType::Cancel) {
+            (add_checked(account.available, amount)?, Decimal::new(0, 0))
+        } else {
+            (account.available, -amount)
+        };
+        account.total = shift_total(account.total, delta, available, account.held, pending)?;
+        account.available = available;
+        account.pending = pending;
+        if tx.tx_type == Some(TxType::Cancel) {
+            pending_tx.tx_type = Some(TxType::Cancel);
+        } else {
+            pending_tx.tx_type = Some(TxType::Withdraw);
+        }
         Ok(())
     }
 
-    fn process_resolve(&mut self, tx: &Transaction) -> Result<(), Box<dyn Error>> {
-        let mut _account = match self.accounts.entry(tx.client) {
-            Occupied(entry) => {
-                if entry.get().locked {
-                    return Err("Account Locked due to Chargeback".into());
-                }
-                entry
-            }
-            Vacant(_) => {
-                return Err("No Associated Client Account Found".into());
-            }
+    /// Remembers insertion order while the store is bounded, evicting the oldest settled
+    /// transactions once it grows past `Config::max_tracked_transactions`. Disputed and pending
+    /// transactions are never evicted as a later resolve, chargeback or settlement needs them.
+    fn track_stored(&mut self, tx_id: u32) {
+        let limit = match self.config.max_tracked_transactions {
+            Some(limit) => limit,
+            None => return,
         };
-        match self.transactions.entry(tx.tx) {
-            Occupied(mut e) => {
-                let disputed_tx = e.get_mut();
-                if !disputed_tx.is_disputed {
-                    return Err("Transaction is not disputed".into());
+        self.tx_order.push_back(tx_id);
+        while self.transactions.len() > limit {
+            let transactions = &self.transactions;
+            let oldest_settled = self.tx_order.iter().position(|id| {
+                transactions
+                    .get(id)
+                    .is_none_or(|t| !t.is_disputed && t.tx_type != Some(TxType::Pending))
+            });
+            match oldest_settled.and_then(|i| self.tx_order.remove(i)) {
+                Some(id) => {
+                    self.transactions.remove(&id);
                 }
-                let account = _account.get_mut();
-                let amount = match disputed_tx.amount {
-                    Some(a) => a,
-                    None => return Err("Amount Required".into()),
-                };
-                account.available += amount;
-                account.held -= amount;
-                disputed_tx.is_disputed = false;
-            }
-            Vacant(_) => {
-                return Err("No Associated Transaction to-be-resolved could be Found".into());
+                None => break,
             }
+        }
+    }
+
+    /// Places or lifts an administrative hold on the account. The row's tx id is not stored.
+    fn process_freeze(&mut self, tx: &Transaction) -> Result<(), ProcessError> {
+        let account = match self.accounts.get_mut(&tx.client) {
+            Some(a) => a,
+            None => return Err(ProcessError::NoSuchAccount),
         };
+        account.frozen = tx.tx_type == Some(TxType::Freeze);
         Ok(())
     }
 
-    fn process_chargeback(&mut self, tx: &Transaction) -> Result<(), Box<dyn Error>> {
-        let mut _account = match self.accounts.entry(tx.client) {
-            Occupied(entry) => {
-                if entry.get().locked {
-                    return Err("Account Locked due to Chargeback".into());
-                }
-                entry
+    /// Looks up the stored transaction a dispute, resolve or chargeback refers to, along with the
+    /// account it is applied against. The row's client must own the transaction, otherwise it
+    /// fails with `ClientMismatch` and no account is touched. If the transaction's own account
+    /// has since been removed it is not re-created, as its balances are gone; the row fails with
+    /// `AccountMissingForTransaction` instead.
+    fn dispute_target(
+        &mut self,
+        tx: &Transaction,
+    ) -> Result<(&mut Transaction, &mut ClientAccount), ProcessError> {
+        let disputed_tx = match self.transactions.get_mut(&tx.tx) {
+            Some(t) => t,
+            None => return Err(ProcessError::TransactionNotFound),
+        };
+        if disputed_tx.client != tx.client {
+            return Err(ProcessError::ClientMismatch);
+        }
+        let account = match self.accounts.get_mut(&tx.client) {
+            Some(a) => a,
+            None => return Err(ProcessError::AccountMissingForTransaction),
+        };
+        if account.locked {
+            return Err(ProcessError::AccountLocked);
+        }
+        Ok((disputed_tx, account))
+    }
+
+    fn process_dispute(&mut self, tx: &Transaction) -> Result<(), ProcessError> {
+        let held_before = self.accounts.get(&tx.client).map(|a| a.held);
+        self.hold_disputed(tx)?;
+        if let Some(at) = tx.timestamp {
+            self.dispute_times.insert(tx.tx, at);
+        }
+        let threshold = match self.config.held_alert_threshold {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+        if let (Some(before), Some(account)) = (held_before, self.accounts.get(&tx.client)) {
+            if before <= threshold && account.held > threshold {
+                self.held_alerts.push(HeldAlert {
+                    client: tx.client,
+                    tx: tx.tx,
+                    held: account.held.amount(),
+                });
             }
-            Vacant(_) => {
-                return Err("No Associated Client Account Found".into());
+        }
+        Ok(())
+    }
+
+    /// Disputing a deposit moves the disputed funds from `available` to `held`. Disputing a
+    /// withdrawal, allowed under `Config::withdrawal_disputes`, instead provisionally reverses the
+    /// debit into `held`, so `available` is unchanged while `total` rises by the disputed amount.
+    fn hold_disputed(&mut self, tx: &Transaction) -> Result<(), ProcessError> {
+        let overhold_policy = if self.config.require_funds_for_dispute {
+            DisputeOverholdPolicy::Reject
+        } else {
+            self.config.dispute_overhold_policy
+        };
+        let withdrawal_disputes = self.config.withdrawal_disputes;
+        let dispute_window = self.config.dispute_window;
+        let (disputed_tx, account) = self.dispute_target(tx)?;
+        let reverses_debit = match disputed_tx.tx_type {
+            Some(TxType::Deposit) => false,
+            Some(TxType::Withdraw) if withdrawal_disputes => true,
+            _ => return Err(ProcessError::NotDisputable),
+        };
+        if let (Some(window), Some(disputed_at), Some(original_at)) =
+            (dispute_window, tx.timestamp, disputed_tx.timestamp)
+        {
+            if disputed_at - original_at > window {
+                return Err(ProcessError::DisputeWindowExpired);
             }
+        }
+        if disputed_tx.is_disputed {
+            return Err(ProcessError::AlreadyDisputed);
+        }
+        let original = match disputed_tx.amount {
+            Some(a) => a,
+            None => return Err(ProcessError::AmountRequired),
         };
-        match self.transactions.entry(tx.tx) {
-            Occupied(mut e) => {
-                let disputed_tx = e.get_mut();
-                if !disputed_tx.is_disputed {
-                    return Err("Transaction is not disputed".into());
+        // A dispute may name part of the original amount; without one the whole amount is held.
+        // Naming nothing would open a dispute which holds no funds.
+        let amount = match tx.amount {
+            Some(a) if a.lt(&Decimal::new(0, 0)) => return Err(ProcessError::NegativeAmount),
+            Some(a) if a.is_zero() => return Err(ProcessError::ZeroAmount),
+            Some(a) if a.gt(&original) => return Err(ProcessError::DisputeExceedsOriginal),
+            Some(a) => a,
+            None => original,
+        };
+        if reverses_debit {
+            let held = add_checked(account.held, amount)?;
+            account.total = shift_total(
+                account.total,
+                amount,
+                account.available,
+                held,
+                account.pending,
+            )?;
+            account.held = held;
+            disputed_tx.is_disputed = true;
+            disputed_tx.disputed_amount = Some(amount);
+            return Ok(());
+        }
+        let amount = if account.available < amount {
+            match overhold_policy {
+                DisputeOverholdPolicy::Allow => amount,
+                DisputeOverholdPolicy::CapAtAvailable => {
+                    account.available.amount().max(Decimal::new(0, 0))
                 }
-                let account = _account.get_mut();
-                let amount = match disputed_tx.amount {
-                    Some(a) => a,
-                    None => return Err("Amount Required".into()),
-                };
-                account.held -= amount;
-                account.total = account.available - account.held;
-                account.locked = true;
-            }
-            Vacant(_) => {
-                return Err("No Associated Transaction to-be-chargedback could be Found".into());
+                DisputeOverholdPolicy::Reject => return Err(ProcessError::DisputeExceedsAvailable),
             }
+        } else {
+            amount
         };
+        let available = sub_checked(account.available, amount)?;
+        account.held = add_checked(account.held, amount)?;
+        account.available = available;
+        disputed_tx.is_disputed = true;
+        disputed_tx.disputed_amount = Some(amount);
         Ok(())
     }
 
-    pub fn process_tx(&mut self, tx: &Transaction) -> Result<(), Box<dyn Error>> {
-        match &tx.tx_type {
-            Some(t) => match t {
-                TxType::Deposit => self.process_deposit(tx)?,
-                TxType::Withdraw => self.process_withdraw(tx)?,
-                TxType::Dispute => self.process_dispute(tx)?,
-                TxType::Resolve => self.process_resolve(tx)?,
-                TxType::Chargeback => self.process_chargeback(tx)?,
-            },
-            None => return Err("No Tx Type provided".into()),
+    /// Releases the held funds: back to `available` for a deposit, or, for a withdrawal, out of
+    /// the account again as the debit stands.
+    fn process_resolve(&mut self, tx: &Transaction) -> Result<(), ProcessError> {
+        let idempotent = self.config.idempotent_resolves;
+        let (disputed_tx, account) = self.dispute_target(tx)?;
+        if !disputed_tx.is_disputed {
+            if idempotent {
+                return Ok(());
+            }
+            return Err(ProcessError::NotDisputed);
+        }
+        let amount = match disputed_tx.disputed_amount.or(disputed_tx.amount) {
+            Some(a) => a,
+            None => return Err(ProcessError::AmountRequired),
         };
+        if account.held.lt(&amount) {
+            return Err(ProcessError::HeldUnderflow);
+        }
+        let held = sub_checked(account.held, amount)?;
+        if disputed_tx.tx_type == Some(TxType::Withdraw) {
+            account.total = shift_total(
+                account.total,
+                -amount,
+                account.available,
+                held,
+                account.pending,
+            )?;
+        } else {
+            account.available = add_checked(account.available, amount)?;
+        }
+        account.held = held;
+        disputed_tx.is_disputed = false;
+        disputed_tx.disputed_amount = None;
+        self.dispute_times.remove(&tx.tx);
         Ok(())
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn deposit_new_account() {
-        let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(1, 0)),
-            is_disputed: false,
+    /// Reverses the disputed transaction & locks the account: a deposit's held funds are removed,
+    /// while a withdrawal's are re-credited to `available`, refunding the client.
+    fn process_chargeback(&mut self, tx: &Transaction) -> Result<(), ProcessError> {
+        let (disputed_tx, account) = self.dispute_target(tx)?;
+        if !disputed_tx.is_disputed {
+            return Err(ProcessError::NotDisputed);
+        }
+        let amount = match disputed_tx.disputed_amount.or(disputed_tx.amount) {
+            Some(a) => a,
+            None => return Err(ProcessError::AmountRequired),
         };
-        let result = acc_man.process_tx(&tx);
-        assert!(result.is_ok());
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_some());
-        let account: &ClientAccount = maybe_account.unwrap();
-        assert_eq!(account.available, Decimal::new(1, 0));
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.held, Decimal::new(0, 0));
-        assert_eq!(account.locked, false);
-        assert_eq!(account.total, Decimal::new(1, 0));
+        // Held can only fall short of a dispute if the account state is already inconsistent.
+        if account.held.lt(&amount) {
+            return Err(ProcessError::HeldUnderflow);
+        }
+        let held = sub_checked(account.held, amount)?;
+        let (available, delta) = if disputed_tx.tx_type == Some(TxType::Withdraw) {
+            (add_checked(account.available, amount)?, Decimal::new(0, 0))
+        } else {
+            (account.available, -amount)
+        };
+        account.total = shift_total(account.total, delta, available, held, account.pending)?;
+        account.available = available;
+        account.held = held;
+        account.locked = true;
+        disputed_tx.charged_back = true;
+        let record = ChargebackRecord {
+            client: account.client,
+            tx: disputed_tx.tx,
+            amount,
+            locked: account.locked,
+        };
+        self.chargebacks.push(record);
+        Ok(())
     }
 
-    #[test]
-    fn deposit_negative_amount_account() {
-        let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(-1, 0)),
-            is_disputed: false,
+    /// Applies the transaction like `process_tx`, reporting how it moved the client's balances
+    /// for change-data-capture.
+    pub fn process_tx_delta(&mut self, tx: &Transaction) -> Result<BalanceDelta, ProcessError> {
+        let balances = |acc_man: &AccountManager| match acc_man.accounts.get(&tx.client) {
+            Some(acc) => (acc.available, acc.held),
+            None => (Money::default(), Money::default()),
         };
-        let result = acc_man.process_tx(&tx);
-        assert!(result.is_err());
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_none());
+        let (available, held) = balances(self);
+        self.process_tx(tx)?;
+        let (new_available, new_held) = balances(self);
+        Ok(BalanceDelta {
+            available: sub_checked(new_available, available)?.amount(),
+            held: sub_checked(new_held, held)?.amount(),
+        })
     }
 
-    #[test]
-    fn withdraw_negative_amount_account() {
-        let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx = Transaction {
-            tx_type: Some(TxType::Withdraw),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(-1, 0)),
-            is_disputed: false,
-        };
-        let result = acc_man.process_tx(&tx);
-        assert!(result.is_err());
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_none());
+    pub fn deposit(&mut self, client: u16, tx: u32, amount: Decimal) -> Result<(), ProcessError> {
+        self.process_tx(&Transaction::new(TxType::Deposit, client, tx, Some(amount)))
     }
 
-    #[test]
-    fn deposit_duplicate_tx() {
-        let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(1, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx2 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(1, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx2).is_err());
+    pub fn withdraw(&mut self, client: u16, tx: u32, amount: Decimal) -> Result<(), ProcessError> {
+        self.process_tx(&Transaction::new(
+            TxType::Withdraw,
+            client,
+            tx,
+            Some(amount),
+        ))
+    }
 
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_some());
-        let account: &ClientAccount = maybe_account.unwrap();
-        assert_eq!(account.available, Decimal::new(1, 0));
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.held, Decimal::new(0, 0));
-        assert_eq!(account.locked, false);
-        assert_eq!(account.total, Decimal::new(1, 0));
+    /// Labels the client's account for grouping; the label is written by the extended schema.
+    pub fn tag(&mut self, client: u16, label: &str) -> Result<(), ProcessError> {
+        match self.accounts.get_mut(&client) {
+            Some(account) => {
+                account.label = Some(label.to_string());
+                Ok(())
+            }
+            None => Err(ProcessError::NoSuchAccount),
+        }
     }
 
-    #[test]
-    fn deposit_multiple_tx() {
-        let mut acc_man = AccountManager::default();
+    /// Zeroes the account's balances as a correction, keeping the account & its locked state and
+    /// recording the previous balances in `corrections`. Refused while the client has an open
+    /// dispute or pending withdrawal, as a later resolve, chargeback or cancel would move funds
+    /// the correction has already written off.
+    pub fn zero_account(&mut self, client: u16) -> Result<(), ProcessError> {
+        if !self.accounts.contains_key(&client) {
+            return Err(ProcessError::NoSuchAccount);
+        }
+        if self.open_disputes().iter().any(|t| t.client == client) {
+            return Err(ProcessError::DisputesOpen);
+        }
+        let pending = self
+            .transactions
+            .values()
+            .any(|t| t.client == client && t.tx_type == Some(TxType::Pending));
+        if pending {
+            return Err(ProcessError::PendingOpen);
+        }
+        let account = match self.accounts.get_mut(&client) {
+            Some(a) => a,
+            None => return Err(ProcessError::NoSuchAccount),
+        };
+        self.corrections.push(CorrectionRecord {
+            client,
+            available: account.available.amount(),
+            held: account.held.amount(),
+            pending: account.pending.amount(),
+            total: account.total.amount(),
+        });
+        let zero = Money::default();
+        account.available = zero;
+        account.held = zero;
+        account.pending = zero;
+        account.total = zero;
+        Ok(())
+    }
+
+    /// Sets the currency code the client's account is held in.
+    pub fn set_currency(&mut self, client: u16, currency: &str) -> Result<(), ProcessError> {
+        match self.accounts.get_mut(&client) {
+            Some(account) => {
+                account.currency = Some(currency.to_string());
+                Ok(())
+            }
+            None => Err(ProcessError::NoSuchAccount),
+        }
+    }
+
+    /// Moves a deposit credited to the wrong client over to `client`, along with its funds, so
+    /// a later dispute, resolve or chargeback is applied against the new owner; one from the old
+    /// owner fails with `ClientMismatch`. A disputed deposit can't be moved, nor can funds the old
+    /// owner no longer has available.
+    pub fn reassign_transaction(&mut self, tx: u32, client: u16) -> Result<(), ProcessError> {
+        let stored = match self.transactions.get(&tx) {
+            Some(t) => t,
+            None => return Err(ProcessError::TransactionNotFound),
+        };
+        if stored.tx_type != Some(TxType::Deposit) {
+            return Err(ProcessError::NotReassignable);
+        }
+        if stored.is_disputed {
+            return Err(ProcessError::AlreadyDisputed);
+        }
+        let amount = match stored.amount {
+            Some(a) => a,
+            None => return Err(ProcessError::AmountRequired),
+        };
+        let owner = stored.client;
+        if owner == client {
+            return Ok(());
+        }
+        let from = match self.accounts.get(&owner) {
+            Some(a) => a,
+            None => return Err(ProcessError::AccountMissingForTransaction),
+        };
+        for account in [Some(from), self.accounts.get(&client)].iter().flatten() {
+            if account.locked {
+                return Err(ProcessError::AccountLocked);
+            }
+            if account.frozen {
+                return Err(ProcessError::AccountFrozen);
+            }
+        }
+        let from_available = sub_checked(from.available, amount)?;
+        if from_available.is_negative() {
+            return Err(ProcessError::InsufficientFunds);
+        }
+        let from_total = shift_total(from.total, -amount, from_available, from.held, from.pending)?;
+        let to = match self.accounts.get(&client) {
+            Some(a) => {
+                let available = add_checked(a.available, amount)?;
+                Some((
+                    available,
+                    shift_total(a.total, amount, available, a.held, a.pending)?,
+                ))
+            }
+            None => None,
+        };
+        if let Some(from) = self.accounts.get_mut(&owner) {
+            from.available = from_available;
+            from.total = from_total;
+        }
+        match (self.accounts.get_mut(&client), to) {
+            (Some(to), Some((available, total))) => {
+                to.available = available;
+                to.total = total;
+            }
+            _ => {
+                let account = ClientAccount::new(client, amount, Decimal::new(0, 0), false)?;
+                self.accounts.insert(client, account);
+            }
+        }
+        if let Some(stored) = self.transactions.get_mut(&tx) {
+            stored.client = client;
+        }
+        Ok(())
+    }
+
+    /// Removes an emptied account, returning it as it stood when closed. An account which still
+    /// holds funds, or has a dispute open, can't be closed; nor can a locked or frozen one, as a
+    /// later deposit would open a fresh account free of the lock. Its transactions are kept, so a
+    /// later dispute of one fails with `AccountMissingForTransaction`.
+    pub fn close_account(&mut self, client: u16) -> Result<ClientAccount, ProcessError> {
+        let account = match self.accounts.get(&client) {
+            Some(a) => a,
+            None => return Err(ProcessError::NoSuchAccount),
+        };
+        if account.locked {
+            return Err(ProcessError::AccountLocked);
+        }
+        if account.frozen {
+            return Err(ProcessError::AccountFrozen);
+        }
+        // Checked ahead of the balances, as an open dispute always leaves funds held.
+        if self.open_disputes().iter().any(|t| t.client == client) {
+            return Err(ProcessError::DisputesOpen);
+        }
+        let zero = Decimal::new(0, 0);
+        if account.available != zero || account.held != zero || account.pending != zero {
+            return Err(ProcessError::BalanceNotZero);
+        }
+        self.accounts
+            .remove(&client)
+            .ok_or(ProcessError::NoSuchAccount)
+    }
+
+    pub fn contains_client(&self, client: u16) -> bool {
+        self.accounts.contains_key(&client)
+    }
+
+    /// Whether the transaction is in the store; evicted transactions are not.
+    pub fn contains_transaction(&self, tx: u32) -> bool {
+        self.transactions.contains_key(&tx)
+    }
+
+    /// Deposits can be disputed, as can withdrawals under `Config::withdrawal_disputes`.
+    fn disputable_type(&self, t: &Transaction) -> bool {
+        match t.tx_type {
+            Some(TxType::Deposit) => true,
+            Some(TxType::Withdraw) => self.config.withdrawal_disputes,
+            _ => false,
+        }
+    }
+
+    /// Whether the transaction is stored, of a disputable type & not already disputed or charged
+    /// back.
+    pub fn is_disputable(&self, tx: u32) -> bool {
+        match self.transactions.get(&tx) {
+            Some(t) => self.disputable_type(t) && !t.is_disputed,
+            None => false,
+        }
+    }
+
+    /// How much of a stored transaction a dispute could still hold: the full amount while
+    /// undisputed, or zero once disputed, even partially, as a transaction is disputed at most
+    /// once. `None` if it can't be disputed at all.
+    pub fn disputable_amount(&self, tx: u32) -> Option<Decimal> {
+        let deposit = match self.transactions.get(&tx) {
+            Some(t) if self.disputable_type(t) => t,
+            _ => return None,
+        };
+        let original = deposit.amount?;
+        if deposit.is_disputed {
+            return Some(Decimal::new(0, 0));
+        }
+        Some(original)
+    }
+
+    /// Resolves every open dispute on the client's account, returning how many were resolved.
+    /// Each resolve goes through `process_tx`, so it is logged & checked like a resolve row.
+    pub fn resolve_all(&mut self, client: u16) -> Result<usize, ProcessError> {
+        match self.accounts.get(&client) {
+            Some(account) if account.locked => return Err(ProcessError::AccountLocked),
+            Some(_) => {}
+            None => return Err(ProcessError::NoSuchAccount),
+        }
+        let mut disputed: Vec<u32> = self
+            .transactions
+            .values()
+            .filter(|t| t.client == client && t.is_disputed)
+            .map(|t| t.tx)
+            .collect();
+        disputed.sort_unstable();
+        for tx_id in &disputed {
+            self.process_tx(&Transaction::new(TxType::Resolve, client, *tx_id, None))?;
+        }
+        Ok(disputed.len())
+    }
+
+    /// The number of the client's deposits in the transaction store.
+    pub fn deposit_count(&self, client: u16) -> usize {
+        self.transactions
+            .values()
+            .filter(|t| t.client == client && t.tx_type == Some(TxType::Deposit))
+            .count()
+    }
+
+    /// Stored transactions grouped by client, each group in tx id order.
+    pub fn transactions_by_client(&self) -> HashMap<u16, Vec<&Transaction>> {
+        let mut by_client: HashMap<u16, Vec<&Transaction>> = HashMap::new();
+        for t in self.transactions.values() {
+            by_client.entry(t.client).or_default().push(t);
+        }
+        for group in by_client.values_mut() {
+            group.sort_unstable_by_key(|t| t.tx);
+        }
+        by_client
+    }
+
+    /// Stored transactions of one type, in no particular order.
+    pub fn transactions_of_type(&self, ty: TxType) -> impl Iterator<Item = &Transaction> + '_ {
+        self.transactions
+            .values()
+            .filter(move |t| t.tx_type.as_ref() == Some(&ty))
+    }
+
+    /// The client's total funds, whether available, held or pending, or `None` if the client has
+    /// no account or the sum overflows.
+    pub fn net_position(&self, client: u16) -> Option<Decimal> {
+        let account = self.accounts.get(&client)?;
+        total_checked(account.available, account.held, account.pending)
+            .ok()
+            .map(Money::amount)
+    }
+
+    /// What the client would have available if every open dispute were resolved in their favour,
+    /// rather than charged back, or `None` if the client has no account or the sum overflows.
+    pub fn projected_available_if_resolved(&self, client: u16) -> Option<Decimal> {
+        let account = self.accounts.get(&client)?;
+        add_checked(account.available, account.held)
+            .ok()
+            .map(Money::amount)
+    }
+
+    /// The portion of the client's held funds which is held because of open disputes, or `None`
+    /// if the client has no account or the sum overflows.
+    pub fn disputed_amount(&self, client: u16) -> Option<Decimal> {
+        if !self.accounts.contains_key(&client) {
+            return None;
+        }
+        let disputed = self
+            .open_disputes()
+            .into_iter()
+            .filter(|t| t.client == client)
+            .filter_map(|t| t.disputed_amount.or(t.amount));
+        sum_checked(disputed).ok().map(Money::amount)
+    }
+
+    pub fn diff(&self, other: &AccountManager) -> Vec<AccountDiff> {
+        fn changed<T: PartialEq>(ours: T, theirs: T) -> Option<(T, T)> {
+            if ours == theirs {
+                None
+            } else {
+                Some((ours, theirs))
+            }
+        }
+        let empty = |client: u16| ClientAccount {
+            client,
+            available: Money::default(),
+            held: Money::default(),
+            pending: Money::default(),
+            total: Money::default(),
+            locked: false,
+            frozen: false,
+            label: None,
+            currency: None,
+        };
+        let mut clients: Vec<u16> = self
+            .accounts
+            .keys()
+            .chain(other.accounts.keys())
+            .copied()
+            .collect();
+        clients.sort_unstable();
+        clients.dedup();
+        clients
+            .into_iter()
+            .filter_map(|client| {
+                let ours = self
+                    .accounts
+                    .get(&client)
+                    .cloned()
+                    .unwrap_or_else(|| empty(client));
+                let theirs = other
+                    .accounts
+                    .get(&client)
+                    .cloned()
+                    .unwrap_or_else(|| empty(client));
+                let diff = AccountDiff {
+                    client,
+                    available: changed(ours.available.amount(), theirs.available.amount()),
+                    held: changed(ours.held.amount(), theirs.held.amount()),
+                    total: changed(ours.total.amount(), theirs.total.amount()),
+                    locked: changed(ours.locked, theirs.locked),
+                };
+                if diff.available.is_none()
+                    && diff.held.is_none()
+                    && diff.total.is_none()
+                    && diff.locked.is_none()
+                {
+                    None
+                } else {
+                    Some(diff)
+                }
+            })
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            accounts: self.accounts.clone(),
+        }
+    }
+
+    /// Owned copies of every account ordered by client id, e.g. to hand to a reporting thread.
+    pub fn snapshot_accounts(&self) -> Vec<ClientAccount> {
+        let mut accounts: Vec<ClientAccount> = self.accounts.values().cloned().collect();
+        accounts.sort();
+        accounts
+    }
+
+    /// Accounts which were opened or changed since the snapshot was taken, ordered by client id.
+    pub fn accounts_changed_since(&self, snapshot: &Snapshot) -> Vec<&ClientAccount> {
+        let mut changed: Vec<&ClientAccount> = self
+            .accounts
+            .values()
+            .filter(|acc| snapshot.accounts.get(&acc.client) != Some(*acc))
+            .collect();
+        changed.sort_by_key(|acc| acc.client);
+        changed
+    }
+
+    /// Replays the events into a fresh manager with the same config and checks that it arrives at
+    /// the same balances & dispute state as this one.
+    pub fn verify_against(&self, events: &[Transaction]) -> bool {
+        let mut replayed = AccountManager::with_config(self.config.clone());
+        for event in events {
+            let _ = replayed.process_tx(event);
+        }
+        self.diff(&replayed).is_empty()
+            && self.transactions.len() == replayed.transactions.len()
+            && self.transactions.iter().all(|(id, t)| {
+                replayed
+                    .transactions
+                    .get(id)
+                    .is_some_and(|r| r.is_disputed == t.is_disputed)
+            })
+    }
+
+    /// Clients whose available funds fall within `[low, high]`, in client id order.
+    pub fn accounts_with_available_between(&self, low: Decimal, high: Decimal) -> Vec<u16> {
+        let mut clients: Vec<u16> = self
+            .accounts
+            .values()
+            .filter(|acc| acc.available >= low && acc.available <= high)
+            .map(|acc| acc.client)
+            .collect();
+        clients.sort_unstable();
+        clients
+    }
+
+    /// Clients whose held ratio is at or above `threshold`, in client id order.
+    pub fn accounts_high_held_ratio(&self, threshold: Decimal) -> Vec<u16> {
+        let mut clients: Vec<u16> = self
+            .accounts
+            .values()
+            .filter(|acc| acc.held_ratio().is_some_and(|ratio| ratio >= threshold))
+            .map(|acc| acc.client)
+            .collect();
+        clients.sort_unstable();
+        clients
+    }
+
+    /// The account with the largest total, the lowest client id winning a tie.
+    pub fn top_account_by_total(&self) -> Option<&ClientAccount> {
+        self.accounts
+            .values()
+            .max_by(|a, b| a.total.cmp(&b.total).then(b.client.cmp(&a.client)))
+    }
+
+    /// Up to `n` accounts with the largest totals, largest first, ties in client id order.
+    pub fn top_n_by_total(&self, n: usize) -> Vec<&ClientAccount> {
+        let mut accounts: Vec<&ClientAccount> = self.accounts.values().collect();
+        accounts.sort_unstable_by(|a, b| b.total.cmp(&a.total).then(a.client.cmp(&b.client)));
+        accounts.truncate(n);
+        accounts
+    }
+
+    pub fn accounts_insolvent(&self) -> Vec<u16> {
+        let mut insolvent: Vec<u16> = self
+            .accounts
+            .values()
+            .filter(|acc| !acc.is_solvent())
+            .map(|acc| acc.client)
+            .collect();
+        insolvent.sort_unstable();
+        insolvent
+    }
+
+    pub fn event_log(&self) -> &[Transaction] {
+        &self.events
+    }
+
+    pub fn chargebacks(&self) -> &[ChargebackRecord] {
+        &self.chargebacks
+    }
+
+    /// Fails with `Overflow` if a sum across the accounts is too large to represent.
+    pub fn stats(&self) -> Result<Stats, ProcessError> {
+        let mut stats = Stats {
+            accounts: self.accounts.len(),
+            disputed_count: self.open_disputes().len(),
+            charged_back_total: self.total_charged_back()?,
+            ..Stats::default()
+        };
+        for account in self.accounts.values() {
+            if account.locked {
+                stats.locked += 1;
+            }
+            stats.total_available = add_checked(stats.total_available, account.available)?.amount();
+            stats.total_held = add_checked(stats.total_held, account.held)?.amount();
+        }
+        Ok(stats)
+    }
+
+    /// Counts & volume of the client's transactions in the store. Fails with `NoSuchAccount` if
+    /// the client has no account, or `Overflow` if the volume is too large to represent.
+    pub fn client_stats(&self, client: u16) -> Result<ClientStats, ProcessError> {
+        if !self.accounts.contains_key(&client) {
+            return Err(ProcessError::NoSuchAccount);
+        }
+        let mut stats = ClientStats::default();
+        for t in self.transactions.values().filter(|t| t.client == client) {
+            match t.tx_type {
+                Some(TxType::Deposit) => stats.deposits += 1,
+                Some(TxType::Withdraw) | Some(TxType::Pending) => stats.withdrawals += 1,
+                _ => continue,
+            }
+            if t.is_disputed && !t.charged_back {
+                stats.disputed += 1;
+            }
+            stats.volume = add_checked(stats.volume, t.amount.unwrap_or_default())?.amount();
+        }
+        Ok(stats)
+    }
+
+    /// The total reversed by chargebacks, whether of a deposit or of a disputed withdrawal. Fails
+    /// with `Overflow` if the total is too large to represent.
+    pub fn total_charged_back(&self) -> Result<Decimal, ProcessError> {
+        sum_checked(self.chargebacks.iter().map(|c| c.amount)).map(Money::amount)
+    }
+
+    /// `total_charged_back` for each client which has had a chargeback.
+    pub fn charged_back_by_client(&self) -> Result<HashMap<u16, Decimal>, ProcessError> {
+        let mut by_client = HashMap::new();
+        for chargeback in &self.chargebacks {
+            let total = by_client
+                .entry(chargeback.client)
+                .or_insert_with(Decimal::default);
+            *total = add_checked(*total, chargeback.amount)?.amount();
+        }
+        Ok(by_client)
+    }
+
+    pub fn held_alerts(&self) -> &[HeldAlert] {
+        &self.held_alerts
+    }
+
+    pub fn corrections(&self) -> &[CorrectionRecord] {
+        &self.corrections
+    }
+
+    pub fn rejections(&self) -> &[Rejection] {
+        &self.rejections
+    }
+
+    pub fn balance_trace(&self) -> &[BalancePoint] {
+        &self.balance_trace
+    }
+
+    /// Transactions refused because their account was locked or frozen, recorded under
+    /// `Config::monitor_locked_accounts`.
+    pub fn locked_violation_attempts(&self) -> &[Transaction] {
+        &self.locked_violations
+    }
+
+    /// Installs a filter which every transaction must pass before it is processed; those which
+    /// fail are rejected as `FilteredOut`.
+    pub fn set_tx_filter(&mut self, f: TxFilter) {
+        self.tx_filter = Some(f);
+    }
+
+    pub fn process_tx(&mut self, tx: &Transaction) -> Result<(), ProcessError> {
+        if let Some(filter) = &self.tx_filter {
+            if !filter(tx) {
+                return Err(ProcessError::FilteredOut);
+            }
+        }
+        if self.config.reserve_client_zero && tx.client == 0 {
+            return Err(ProcessError::ReservedClient);
+        }
+        let applied = match &tx.tx_type {
+            Some(t) => match t {
+                TxType::Deposit => self.process_deposit(tx),
+                TxType::Withdraw => self.process_withdraw(tx),
+                TxType::Dispute => self.process_dispute(tx),
+                TxType::Resolve => self.process_resolve(tx),
+                TxType::Chargeback => self.process_chargeback(tx),
+                TxType::Pending => self.process_withdraw(tx),
+                TxType::Settle | TxType::Cancel => self.process_settlement(tx),
+                TxType::Freeze | TxType::Unfreeze => self.process_freeze(tx),
+                TxType::Unknown(name) => match self.config.unknown_type_policy {
+                    UnknownTypePolicy::Error => Err(ProcessError::UnknownTxType(name.clone())),
+                    UnknownTypePolicy::Skip => return Ok(()),
+                },
+            },
+            None => Err(ProcessError::MissingTxType),
+        };
+        if let Err(ProcessError::AccountLocked) | Err(ProcessError::AccountFrozen) = applied {
+            if self.config.monitor_locked_accounts {
+                self.locked_violations.push(tx.clone());
+            }
+        }
+        applied?;
+        if self.config.debug_checks {
+            if let Some(account) = self.accounts.get(&tx.client) {
+                account.check_invariant()?;
+            }
+        }
+        if self.config.record_events {
+            self.events.push(tx.clone());
+        }
+        if self.config.trace_balances {
+            if let Some(account) = self.accounts.get(&tx.client) {
+                self.balance_trace.push(BalancePoint {
+                    tx: tx.tx,
+                    client: tx.client,
+                    available: account.available.amount(),
+                    held: account.held.amount(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the transactions as one group: if any fails, every change made by the group is
+    /// undone and that failure is returned. A bounded transaction store is saved whole, as
+    /// eviction may drop transactions the group never mentions.
+    pub fn process_atomic(&mut self, txns: &[Transaction]) -> Result<(), ProcessError> {
+        let accounts: Vec<(u16, Option<ClientAccount>)> = txns
+            .iter()
+            .map(|t| (t.client, self.accounts.get(&t.client).cloned()))
+            .collect();
+        let transactions: Vec<(u32, Option<Transaction>)> = txns
+            .iter()
+            .map(|t| (t.tx, self.transactions.get(&t.tx).cloned()))
+            .collect();
+        let dispute_times: Vec<(u32, Option<DateTime<Utc>>)> = txns
+            .iter()
+            .map(|t| (t.tx, self.dispute_times.get(&t.tx).copied()))
+            .collect();
+        let bounded_store = self
+            .config
+            .max_tracked_transactions
+            .map(|_| (self.transactions.clone(), self.tx_order.clone()));
+        let logged = (
+            self.events.len(),
+            self.chargebacks.len(),
+            self.balance_trace.len(),
+            self.held_alerts.len(),
+            self.locked_violations.len(),
+        );
+        for tx in txns {
+            let e = match self.process_tx(tx) {
+                Ok(_) => continue,
+                Err(e) => e,
+            };
+            for (client, account) in accounts.into_iter().rev() {
+                match account {
+                    Some(a) => self.accounts.insert(client, a),
+                    None => self.accounts.remove(&client),
+                };
+            }
+            match bounded_store {
+                Some((store, order)) => {
+                    self.transactions = store;
+                    self.tx_order = order;
+                }
+                None => {
+                    for (id, stored) in transactions.into_iter().rev() {
+                        match stored {
+                            Some(t) => self.transactions.insert(id, t),
+                            None => self.transactions.remove(&id),
+                        };
+                    }
+                }
+            }
+            for (id, at) in dispute_times.into_iter().rev() {
+                match at {
+                    Some(at) => self.dispute_times.insert(id, at),
+                    None => self.dispute_times.remove(&id),
+                };
+            }
+            self.events.truncate(logged.0);
+            self.chargebacks.truncate(logged.1);
+            self.balance_trace.truncate(logged.2);
+            self.held_alerts.truncate(logged.3);
+            self.locked_violations.truncate(logged.4);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Applies each transaction in turn, handing any which fail to `on_error` along with the
+    /// reason. A building block for readers of formats other than CSV.
+    pub fn process_records<I, F>(&mut self, records: I, mut on_error: F)
+    where
+        I: IntoIterator<Item = Transaction>,
+        F: FnMut(&Transaction, ProcessError),
+    {
+        for tx in records {
+            if let Err(e) = self.process_tx(&tx) {
+                on_error(&tx, e);
+            }
+        }
+    }
+
+    /// Like `process_records`, but first orders the batch by timestamp so rows needn't arrive in
+    /// order. Transactions without a timestamp go first, and those sharing a timestamp are
+    /// ordered by tx id so the outcome doesn't depend on input order. Rows sharing both, such as
+    /// a deposit with its dispute & resolve, keep their input order as the sort is stable.
+    pub fn process_batch<F>(&mut self, mut txns: Vec<Transaction>, on_error: F)
+    where
+        F: FnMut(&Transaction, ProcessError),
+    {
+        txns.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then(a.tx.cmp(&b.tx)));
+        self.process_records(txns, on_error);
+    }
+
+    /// Processes a CSV file, timing the run; see `last_run_stats`.
+    pub fn process_file(&mut self, csv_path: &str) -> Result<ProcessSummary, Box<dyn Error>> {
+        let started = Instant::now();
+        let mut tx_stream = self.tx_stream(io::BufReader::new(File::open(csv_path)?));
+        let summary = self.process_stream(&mut tx_stream)?;
+        self.last_run_stats = Some(RunStats {
+            rows: summary.rows,
+            duration: started.elapsed(),
+        });
+        Ok(summary)
+    }
+
+    /// Resumes processing a CSV file from `offset`, which must be the start of a record, e.g. after
+    /// a crash part way through a huge file. The file's header is still used, and rejections are
+    /// numbered by line from the offset rather than from the start of the file.
+    pub fn process_file_from_offset<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+        offset: u64,
+    ) -> Result<ProcessSummary, Box<dyn Error>> {
+        let mut file = io::BufReader::new(File::open(path)?);
+        let mut header = String::new();
+        file.read_line(&mut header)?;
+        if offset > header.len() as u64 {
+            file.seek(io::SeekFrom::Start(offset))?;
+        }
+        let mut tx_stream = self.tx_stream(header.as_bytes().chain(file));
+        self.process_stream(&mut tx_stream)
+    }
+
+    fn tx_stream<R: io::Read>(&self, reader: R) -> TxStreamReader<R> {
+        TxStreamReader::new_from_reader_with_delimiter(reader, self.config.delimiter)
+    }
+
+    pub fn last_run_stats(&self) -> Option<RunStats> {
+        self.last_run_stats.clone()
+    }
+
+    pub fn process_csv<R: io::Read>(
+        &mut self,
+        reader: R,
+    ) -> Result<ProcessSummary, Box<dyn Error>> {
+        let mut tx_stream = self.tx_stream(reader);
+        self.process_stream(&mut tx_stream)
+    }
+
+    /// Processes a CSV, then writes the accounts to `out` and a diagnostic line for each row
+    /// rejected by this run to `err`, so a pipeline can keep the two apart.
+    pub fn process_csv_to<R: io::Read, O: io::Write, E: io::Write>(
+        &mut self,
+        reader: R,
+        out: O,
+        mut err: E,
+    ) -> Result<ProcessSummary, Box<dyn Error>> {
+        let earlier = self.rejections.len();
+        let summary = self.process_csv(reader)?;
+        for rejection in &self.rejections[earlier..] {
+            writeln!(err, "Error: {}", rejection)?;
+        }
+        err.flush()?;
+        self.write_csv(out)?;
+        Ok(summary)
+    }
+
+    /// Processes a CSV, skipping deposits & withdrawals whose tx id is in `seen`, i.e. was applied
+    /// by an earlier run, so an interrupted ingestion can be resumed from the start of its input.
+    pub fn process_csv_with_dedup<R: io::Read>(
+        &mut self,
+        reader: R,
+        seen: HashSet<u32>,
+    ) -> Result<ProcessSummary, Box<dyn Error>> {
+        self.seen_tx_ids = seen;
+        let summary = self.process_csv(reader);
+        self.seen_tx_ids.clear();
+        summary
+    }
+
+    /// Processes no more than the first `max_rows` records, e.g. to sample a huge file.
+    pub fn process_csv_limited<R: io::Read>(
+        &mut self,
+        reader: R,
+        max_rows: usize,
+    ) -> Result<ProcessSummary, Box<dyn Error>> {
+        let mut tx_stream = self.tx_stream(reader);
+        self.process_rows(&mut tx_stream, max_rows, &mut |_, _| Ok(()))
+    }
+
+    /// Processes a CSV applying at most `per_sec` transactions a second, sleeping between them as
+    /// needed, e.g. to replay into a downstream system at a controlled rate. `0` means no limit.
+    pub fn process_csv_throttled<R: io::Read>(
+        &mut self,
+        reader: R,
+        per_sec: u32,
+    ) -> Result<ProcessSummary, Box<dyn Error>> {
+        if per_sec == 0 {
+            return self.process_csv(reader);
+        }
+        let interval = Duration::from_secs(1) / per_sec;
+        let mut tx_stream = self.tx_stream(reader);
+        let mut next = Instant::now();
+        self.process_rows(&mut tx_stream, usize::MAX, &mut |_, _| {
+            next += interval;
+            let now = Instant::now();
+            if next > now {
+                std::thread::sleep(next - now);
+            }
+            Ok(())
+        })
+    }
+
+    /// Processes a CSV, writing the account's updated row to `writer` after every applied
+    /// transaction, e.g. to feed a live dashboard.
+    pub fn process_streaming<R: io::Read, W: io::Write>(
+        &mut self,
+        reader: R,
+        writer: W,
+    ) -> Result<ProcessSummary, Box<dyn Error>> {
+        let mut tx_stream = self.tx_stream(reader);
+        let mut wtr = self.csv_writer(writer);
+        self.process_rows(&mut tx_stream, usize::MAX, &mut |acc_man, tx| {
+            if let Some(acc) = acc_man.accounts.get(&tx.client) {
+                acc_man.write_account(&mut wtr, acc)?;
+                wtr.flush()?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Applies every row of the stream, recording rows which cannot be applied as `Rejection`s
+    /// rather than aborting. Only an underlying I/O failure stops ingestion early.
+    pub fn process_stream<R: io::Read>(
+        &mut self,
+        tx_stream: &mut TxStreamReader<R>,
+    ) -> Result<ProcessSummary, Box<dyn Error>> {
+        self.process_rows(tx_stream, usize::MAX, &mut |_, _| Ok(()))
+    }
+
+    /// Ingests up to `max_rows` rows, calling `on_applied` after each one which applies.
+    fn process_rows<R: io::Read>(
+        &mut self,
+        tx_stream: &mut TxStreamReader<R>,
+        max_rows: usize,
+        on_applied: &mut OnApplied,
+    ) -> Result<ProcessSummary, Box<dyn Error>> {
+        let mut summary = ProcessSummary::default();
+        // Rows map onto `Transaction` by position rather than by header name, so header text,
+        // including any UTF-8 BOM a spreadsheet export leaves on it, cannot affect parsing.
+        for buf in tx_stream.stream.byte_records().take(max_rows) {
+            summary.rows += 1;
+            let record = match buf {
+                Ok(record) => record,
+                Err(e) => {
+                    if let csv::ErrorKind::Io(_) = e.kind() {
+                        return Err(e.into());
+                    }
+                    let row = e.position().map_or(0, |p| p.line());
+                    self.reject(
+                        &mut summary,
+                        row,
+                        None,
+                        ProcessError::MalformedRow(e.to_string()),
+                    );
+                    continue;
+                }
+            };
+            let row = record.position().map_or(0, |p| p.line());
+            let record = match AccountManager::decode_record(record) {
+                Ok(record) => record,
+                Err(e) => {
+                    self.reject(&mut summary, row, None, e);
+                    continue;
+                }
+            };
+            if let Err(e) = AccountManager::validate_record(&record) {
+                self.reject(&mut summary, row, None, e);
+                continue;
+            }
+            let record = match self.normalize_amount(record) {
+                Ok(record) => record,
+                Err(e) => {
+                    self.reject(&mut summary, row, None, e);
+                    continue;
+                }
+            };
+            let deserialized_tx = match record.deserialize::<Transaction>(None) {
+                Ok(tx) => tx,
+                Err(e) => {
+                    self.reject(
+                        &mut summary,
+                        row,
+                        None,
+                        ProcessError::MalformedRow(e.to_string()),
+                    );
+                    continue;
+                }
+            };
+            if let Some(tx) = self.apply_row(&mut summary, row, deserialized_tx) {
+                on_applied(self, &tx)?;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Processes fixed-width records, one per line, with fields where `layout` places them.
+    /// Blank lines are skipped; like CSV rows, lines which cannot be applied become `Rejection`s.
+    pub fn process_fixed_width<R: io::Read>(
+        &mut self,
+        reader: R,
+        layout: FixedLayout,
+    ) -> Result<ProcessSummary, Box<dyn Error>> {
+        let mut summary = ProcessSummary::default();
+        for (i, line) in io::BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            summary.rows += 1;
+            let row = i as u64 + 1;
+            match layout.parse_line(&line) {
+                Ok(tx) => {
+                    self.apply_row(&mut summary, row, tx);
+                }
+                Err(e) => self.reject(&mut summary, row, None, e),
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Processes a Parquet file with `type, client, tx, amount` columns. A row which can't be
+    /// read from the file ends the run with an error, as an I/O error does for a CSV.
+    #[cfg(feature = "parquet")]
+    pub fn process_parquet<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<ProcessSummary, Box<dyn Error>> {
+        let mut summary = ProcessSummary::default();
+        for (i, row) in crate::parquet_input::rows(path.as_ref())?.enumerate() {
+            let row_data = row?;
+            summary.rows += 1;
+            let row = i as u64 + 1;
+            match crate::parquet_input::parse_row(&row_data) {
+                Ok(tx) => {
+                    self.apply_row(&mut summary, row, tx);
+                }
+                Err(e) => self.reject(&mut summary, row, None, e),
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Applies one parsed input row, recording it in the summary either way. Returns the
+    /// transaction if it was applied.
+    fn apply_row(
+        &mut self,
+        summary: &mut ProcessSummary,
+        row: u64,
+        mut tx: Transaction,
+    ) -> Option<Transaction> {
+        let rounded = tx.amount.map(|a| a.round_dp(DECIMAL_PRECISION));
+        if self.config.strict_precision && rounded != tx.amount {
+            self.reject(
+                summary,
+                row,
+                Some(&tx),
+                ProcessError::TooPrecise(DECIMAL_PRECISION),
+            );
+            return None;
+        }
+        tx.amount = rounded;
+        let originates = matches!(
+            tx.tx_type,
+            Some(TxType::Deposit) | Some(TxType::Withdraw) | Some(TxType::Pending)
+        );
+        if originates && self.seen_tx_ids.contains(&tx.tx) {
+            summary.skipped += 1;
+            return None;
+        }
+        let unknown = matches!(tx.tx_type, Some(TxType::Unknown(_)));
+        if unknown && self.config.unknown_type_policy == UnknownTypePolicy::Skip {
+            summary.skipped += 1;
+            return None;
+        }
+        match self.process_tx(&tx) {
+            Ok(_) => {
+                summary.applied += 1;
+                Some(tx)
+            }
+            Err(e) => {
+                if self.config.strict_spec && self.disputes_withdrawal(&tx, &e) {
+                    summary.withdrawal_disputes_rejected += 1;
+                }
+                self.reject(summary, row, Some(&tx), e);
+                None
+            }
+        }
+    }
+
+    /// Rewrites the amount per `Config::decimal_format` into the plain form `Decimal` parses,
+    /// e.g. `1.000,50` or `1,000.50` into `1000.50`. Minor units are combined with the scale
+    /// column, which is then dropped so the remaining fields keep their usual positions.
+    fn normalize_amount(
+        &self,
+        record: csv::StringRecord,
+    ) -> Result<csv::StringRecord, ProcessError> {
+        let amount = match record.get(AMOUNT_FIELD) {
+            Some(a) => a,
+            None => return Ok(record),
+        };
+        let normalized = match self.config.decimal_format {
+            DecimalFormat::Dot => AccountManager::ungroup(amount, ',', '.')?,
+            DecimalFormat::Comma => AccountManager::ungroup(amount, '.', ',')?,
+            DecimalFormat::MinorUnits => {
+                let scale = record.get(AMOUNT_FIELD + 1).unwrap_or_default();
+                AccountManager::from_minor_units(amount, scale)?
+            }
+        };
+        let minor_units = self.config.decimal_format == DecimalFormat::MinorUnits;
+        if normalized == amount && !minor_units {
+            return Ok(record);
+        }
+        let mut rewritten: csv::StringRecord = record
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !minor_units || *i != AMOUNT_FIELD + 1)
+            .map(|(i, field)| {
+                if i == AMOUNT_FIELD {
+                    &normalized
+                } else {
+                    field
+                }
+            })
+            .collect();
+        rewritten.set_position(record.position().cloned());
+        Ok(rewritten)
+    }
+
+    /// Strips the thousands separator `group` and writes the `decimal` separator as `.`. Only
+    /// separators splitting the whole part into groups of three digits are accepted, so `1,5`
+    /// fails rather than becoming `15`.
+    fn ungroup(amount: &str, group: char, decimal: char) -> Result<String, ProcessError> {
+        let invalid = || ProcessError::MalformedRow(format!("invalid amount: {}", amount));
+        let (whole, fraction) = match amount.split_once(decimal) {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (amount, None),
+        };
+        if fraction.is_some_and(|f| f.contains(group) || f.contains(decimal)) {
+            return Err(invalid());
+        }
+        if whole.contains(group) {
+            let digits = whole.trim_start_matches(&['-', '+'][..]);
+            let mut groups = digits.split(group);
+            let lead = groups.next().unwrap_or_default();
+            let is_digits = |g: &str| g.bytes().all(|b| b.is_ascii_digit());
+            if lead.is_empty() || lead.len() > 3 || !is_digits(lead) {
+                return Err(invalid());
+            }
+            if !groups.all(|g| g.len() == 3 && is_digits(g)) {
+                return Err(invalid());
+            }
+        }
+        let mut normalized = whole.replace(group, "");
+        if let Some(fraction) = fraction {
+            normalized.push('.');
+            normalized.push_str(fraction);
+        }
+        Ok(normalized)
+    }
+
+    /// An integer amount of minor units as a decimal, e.g. `150` at scale `2` as `1.50`. An empty
+    /// amount, as on a dispute, stays empty.
+    fn from_minor_units(amount: &str, scale: &str) -> Result<String, ProcessError> {
+        if amount.is_empty() {
+            return Ok(String::new());
+        }
+        let units = amount
+            .parse::<i64>()
+            .map_err(|e| ProcessError::MalformedRow(format!("invalid minor units: {}", e)))?;
+        let scale = scale
+            .parse::<u32>()
+            .map_err(|e| ProcessError::MalformedRow(format!("invalid scale: {}", e)))?;
+        Decimal::try_new(units, scale)
+            .map(|d| d.to_string())
+            .map_err(|e| ProcessError::MalformedRow(format!("invalid scale: {}", e)))
+    }
+
+    /// Decodes a row as UTF-8. Invalid bytes in an ignored trailing column can't affect the
+    /// transaction, so they are replaced rather than rejecting the row.
+    fn decode_record(record: csv::ByteRecord) -> Result<csv::StringRecord, ProcessError> {
+        match csv::StringRecord::from_byte_record(record) {
+            Ok(record) => Ok(record),
+            Err(e) if e.utf8_error().field() < TRANSACTION_FIELDS => {
+                Err(ProcessError::MalformedRow(format!(
+                    "invalid UTF-8 in field {}",
+                    e.utf8_error().field()
+                )))
+            }
+            Err(e) => Ok(csv::StringRecord::from_byte_record_lossy(
+                e.into_byte_record(),
+            )),
+        }
+    }
+
+    /// Catches problems with a raw row which deserialization would otherwise report opaquely,
+    /// or silently paper over with a default.
+    fn validate_record(record: &csv::StringRecord) -> Result<(), ProcessError> {
+        if record.len() < 3 {
+            let reason = format!("expected at least 3 fields, found {}", record.len());
+            return Err(ProcessError::MalformedRow(reason));
+        }
+        let client = &record[CLIENT_FIELD];
+        if client.parse::<u16>().is_err() {
+            if !client.is_empty() && client.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ProcessError::ClientIdOutOfRange(client.to_string()));
+            }
+            return Err(ProcessError::InvalidClientId(client.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Whether the error is a dispute refused because it targets a withdrawal.
+    fn disputes_withdrawal(&self, tx: &Transaction, e: &ProcessError) -> bool {
+        *e == ProcessError::NotDisputable
+            && self.transactions.get(&tx.tx).is_some_and(|t| {
+                matches!(t.tx_type, Some(TxType::Withdraw) | Some(TxType::Pending))
+            })
+    }
+
+    fn reject(
+        &mut self,
+        summary: &mut ProcessSummary,
+        row: u64,
+        tx: Option<&Transaction>,
+        reason: ProcessError,
+    ) {
+        summary.rejected += 1;
+        self.rejections.push(Rejection {
+            row,
+            tx: tx.map(|t| t.tx),
+            client: tx.map(|t| t.client),
+            tx_type: tx.and_then(|t| t.tx_type.clone()),
+            reason,
+        });
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::assertions_on_constants)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use chrono::Utc;
+
+    #[test]
+    fn deposit_new_account() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        let result = acc_man.process_tx(&tx);
+        assert!(result.is_ok());
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_some());
+        let account: &ClientAccount = maybe_account.unwrap();
+        assert_eq!(account.available, Decimal::new(1, 0));
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.locked, false);
+        assert_eq!(account.total, Decimal::new(1, 0));
+    }
+
+    #[test]
+    fn deposit_negative_amount_account() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(-1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        let result = acc_man.process_tx(&tx);
+        assert!(result.is_err());
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_none());
+    }
+
+    #[test]
+    fn withdraw_negative_amount_account() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx = Transaction {
+            tx_type: Some(TxType::Withdraw),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(-1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        let result = acc_man.process_tx(&tx);
+        assert!(result.is_err());
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_none());
+    }
+
+    #[test]
+    fn deposit_duplicate_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_err());
+
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_some());
+        let account: &ClientAccount = maybe_account.unwrap();
+        assert_eq!(account.available, Decimal::new(1, 0));
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.locked, false);
+        assert_eq!(account.total, Decimal::new(1, 0));
+    }
+
+    #[test]
+    fn deposit_multiple_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 2u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_ok());
+
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_some());
+        let account: &ClientAccount = maybe_account.unwrap();
+        assert_eq!(account.available, Decimal::new(2, 0));
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.locked, false);
+        assert_eq!(account.total, Decimal::new(2, 0));
+    }
+
+    #[test]
+    fn withdraw_new_account() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx = Transaction {
+            tx_type: Some(TxType::Withdraw),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        let result = acc_man.process_tx(&tx);
+        assert!(result.is_err());
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_none());
+    }
+
+    #[test]
+    fn withdrawal_requires_an_existing_account() {
+        let mut acc_man = AccountManager::default();
+        let tx = Transaction::new(TxType::Withdraw, 1, 1, Some(Decimal::new(1, 0)));
+        assert_eq!(acc_man.process_tx(&tx), Err(ProcessError::NoSuchAccount));
+        assert!(!acc_man.contains_client(1));
+        assert!(!acc_man.contains_transaction(1));
+
+        let mut lenient = AccountManager::with_config(Config {
+            withdrawal_requires_account: false,
+            ..Config::default()
+        });
+        assert_eq!(
+            lenient.process_tx(&tx),
+            Err(ProcessError::InsufficientFunds)
+        );
+        assert!(!lenient.contains_client(1));
+        assert!(!lenient.contains_transaction(1));
+        let tx = Transaction::new(TxType::Withdraw, 1, 1, Some(Decimal::new(0, 0)));
+        assert!(lenient.process_tx(&tx).is_ok());
+        assert_eq!(lenient.accounts[&1u16].total, Decimal::new(0, 0));
+
+        assert!(acc_man.deposit(1, 2, Decimal::new(3, 0)).is_ok());
+        let tx = Transaction::new(TxType::Withdraw, 1, 3, Some(Decimal::new(1, 0)));
+        assert!(acc_man.process_tx(&tx).is_ok());
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(2, 0));
+    }
+
+    #[test]
+    fn withdraw_duplicate_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(9, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx).is_ok());
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Withdraw),
+            client: client_id,
+            tx: 2u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Withdraw),
+            client: client_id,
+            tx: 2u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_err());
+
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_some());
+        let account: &ClientAccount = maybe_account.unwrap();
+        assert_eq!(account.available, Decimal::new(8, 0));
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.locked, false);
+        assert_eq!(account.total, Decimal::new(8, 0));
+    }
+
+    #[test]
+    fn withdraw_multiple_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(10, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Withdraw),
+            client: client_id,
+            tx: 2u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_ok());
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Withdraw),
+            client: client_id,
+            tx: 3u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_ok());
+
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_some());
+        let account: &ClientAccount = maybe_account.unwrap();
+        assert_eq!(account.available, Decimal::new(8, 0));
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.locked, false);
+        assert_eq!(account.total, Decimal::new(8, 0));
+    }
+
+    #[test]
+    fn withdraw_insufficient_funds_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(10, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Withdraw),
+            client: client_id,
+            tx: 2u32,
+            amount: Some(Decimal::new(11, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_err());
+
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_some());
+        let account: &ClientAccount = maybe_account.unwrap();
+        assert_eq!(account.available, Decimal::new(10, 0));
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.locked, false);
+        assert_eq!(account.total, Decimal::new(10, 0));
+    }
+
+    #[test]
+    fn dispute_a_deposit_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_ok());
+
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_some());
+        let account: &ClientAccount = maybe_account.unwrap();
+        assert_eq!(account.available, Decimal::new(0, 0));
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.held, Decimal::new(5, 0));
+        assert_eq!(account.locked, false);
+        assert_eq!(account.total, Decimal::new(5, 0));
+        match acc_man.transactions.entry(1u32) {
+            Occupied(e) => assert_eq!(e.get().is_disputed, true),
+            Vacant(_e) => assert!(false),
+        };
+    }
+
+    #[test]
+    fn dispute_a_withdraw_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(10, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx).is_ok());
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Withdraw),
+            client: client_id,
+            tx: 2u32,
+            amount: Some(Decimal::new(9, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 2u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_err());
+
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_some());
+        let account: &ClientAccount = maybe_account.unwrap();
+        assert_eq!(account.available, Decimal::new(1, 0));
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.locked, false);
+        assert_eq!(account.total, Decimal::new(1, 0));
+        match acc_man.transactions.entry(1u32) {
+            Occupied(e) => assert_eq!(e.get().is_disputed, false),
+            Vacant(_e) => assert!(false),
+        };
+    }
+
+    #[test]
+    fn resolve_a_dispute_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(9, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_ok());
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Resolve),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_ok());
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_some());
+        let account: &ClientAccount = maybe_account.unwrap();
+        assert_eq!(account.available, Decimal::new(9, 0));
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.locked, false);
+        assert_eq!(account.total, Decimal::new(9, 0));
+        match acc_man.transactions.entry(1u32) {
+            Occupied(e) => assert_eq!(e.get().is_disputed, false),
+            Vacant(_e) => assert!(false),
+        };
+    }
+
+    #[test]
+    fn resolve_a_non_dispute_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(9, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Resolve),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_err());
+    }
+
+    #[test]
+    fn chargeback_a_dispute_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(9, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_ok());
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Chargeback),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_ok());
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_some());
+        let account: &ClientAccount = maybe_account.unwrap();
+        assert_eq!(account.available, Decimal::new(0, 0));
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.locked, true);
+        assert_eq!(account.total, Decimal::new(0, 0));
+        match acc_man.transactions.entry(1u32) {
+            Occupied(e) => assert_eq!(e.get().is_disputed, true),
+            Vacant(_e) => assert!(false),
+        };
+    }
+
+    #[test]
+    fn cant_deposit_to_a_locked_account() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(9, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_ok());
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Chargeback),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_ok());
+        let tx4 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(9, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx4).is_err());
+        let maybe_account = acc_man.accounts.get(&client_id);
+        assert!(maybe_account.is_some());
+        let account: &ClientAccount = maybe_account.unwrap();
+        assert_eq!(account.available, Decimal::new(0, 0));
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.locked, true);
+        assert_eq!(account.total, Decimal::new(0, 0));
+        match acc_man.transactions.entry(1u32) {
+            Occupied(e) => assert_eq!(e.get().is_disputed, true),
+            Vacant(_e) => assert!(false),
+        };
+    }
+
+    #[test]
+    fn chargeback_a_non_dispute_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(9, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Chargeback),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_err());
+    }
+
+    #[test]
+    fn chargeback_a_non_existent_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(9, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Chargeback),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_err());
+    }
+
+    #[test]
+    fn chargeback_a_non_existent_customer() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Chargeback),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_err());
+    }
+
+    #[test]
+    fn resolve_a_non_existent_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Resolve),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_err());
+    }
+
+    #[test]
+    fn dispute_a_non_existent_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_err());
+    }
+
+    #[test]
+    fn insolvent_account_after_dispute_of_withdrawn_funds() {
+        let mut acc_man = AccountManager::default();
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: 1u16,
+            tx: 1u32,
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: 2u16,
+            tx: 2u32,
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_ok());
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Withdraw),
+            client: 1u16,
+            tx: 3u32,
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_ok());
+        let tx4 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: 1u16,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx4).is_ok());
+
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(-5, 0));
+        assert!(!account.is_solvent());
+        assert!(acc_man.accounts.get(&2u16).unwrap().is_solvent());
+        assert_eq!(acc_man.accounts_insolvent(), vec![1u16]);
+    }
+
+    #[test]
+    fn malformed_row_is_rejected_and_ingestion_continues() {
+        let mut acc_man = AccountManager::default();
+        let csv = "type, client, tx, amount\ndeposit, 1\ndeposit, 1, 2, 1.5\n";
+        let summary = acc_man.process_csv(csv.as_bytes()).unwrap();
+        assert_eq!(summary.rows, 2);
+        assert_eq!(summary.applied, 1);
+        assert_eq!(summary.rejected, 1);
+
+        let rejections = acc_man.rejections();
+        assert_eq!(rejections.len(), 1);
+        assert_eq!(rejections[0].row, 2);
+        assert!(matches!(
+            rejections[0].reason,
+            ProcessError::MalformedRow(_)
+        ));
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(15, 1));
+    }
+
+    #[test]
+    fn resolve_all_disputes_for_a_client() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        for (tx_id, amount) in &[(1u32, 3i64), (2u32, 5i64)] {
+            let deposit = Transaction {
+                tx_type: Some(TxType::Deposit),
+                client: client_id,
+                tx: *tx_id,
+                amount: Some(Decimal::new(*amount, 0)),
+                timestamp: None,
+                is_disputed: false,
+                disputed_amount: None,
+                charged_back: false,
+            };
+            assert!(acc_man.process_tx(&deposit).is_ok());
+            let dispute = Transaction {
+                tx_type: Some(TxType::Dispute),
+                client: client_id,
+                tx: *tx_id,
+                amount: None,
+                timestamp: None,
+                is_disputed: false,
+                disputed_amount: None,
+                charged_back: false,
+            };
+            assert!(acc_man.process_tx(&dispute).is_ok());
+        }
+        assert_eq!(
+            acc_man.accounts.get(&client_id).unwrap().held,
+            Decimal::new(8, 0)
+        );
+
+        assert_eq!(acc_man.resolve_all(client_id), Ok(2));
+        let account: &ClientAccount = acc_man.accounts.get(&client_id).unwrap();
+        assert_eq!(account.available, Decimal::new(8, 0));
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(acc_man.resolve_all(client_id), Ok(0));
+        assert_eq!(acc_man.resolve_all(2u16), Err(ProcessError::NoSuchAccount));
+    }
+
+    #[test]
+    fn tx_types_are_case_insensitive() {
+        let mut acc_man = AccountManager::default();
+        let csv = "type, client, tx, amount\n\
+                   Deposit, 1, 1, 5.0\n\
+                   DEPOSIT, 1, 2, 5.0\n\
+                   WithDraw, 1, 3, 2.0\n\
+                   DISPUTE, 1, 1,\n";
+        let summary = acc_man.process_csv(csv.as_bytes()).unwrap();
+        assert_eq!(summary.applied, 4);
+        assert!(acc_man.rejections().is_empty());
+
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(3, 0));
+        assert_eq!(account.held, Decimal::new(5, 0));
+    }
+
+    #[test]
+    fn chargeback_is_recorded_for_audit() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 7u32,
+            amount: Some(Decimal::new(42, 1)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 7u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_ok());
+        assert!(acc_man.chargebacks().is_empty());
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Chargeback),
+            client: client_id,
+            tx: 7u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_ok());
+
+        let records = acc_man.chargebacks();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].client, client_id);
+        assert_eq!(records[0].tx, 7u32);
+        assert_eq!(records[0].amount, Decimal::new(42, 1));
+        assert!(records[0].locked);
+    }
+
+    #[test]
+    fn dispute_with_missing_account_for_existing_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        acc_man.accounts.remove(&client_id);
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert_eq!(
+            acc_man.process_tx(&tx2),
+            Err(ProcessError::AccountMissingForTransaction)
+        );
+        for tx_type in [TxType::Resolve, TxType::Chargeback] {
+            assert_eq!(
+                acc_man.process_tx(&Transaction::new(tx_type, client_id, 1, None)),
+                Err(ProcessError::AccountMissingForTransaction)
+            );
+        }
+        assert!(!acc_man.contains_client(client_id));
+    }
+
+    #[test]
+    fn dispute_by_a_client_not_owning_the_tx() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 5.0\n\
+             deposit, 2, 2, 3.0\n",
+        )
+        .unwrap();
+        for tx_type in [TxType::Dispute, TxType::Resolve, TxType::Chargeback] {
+            assert_eq!(
+                acc_man.process_tx(&Transaction::new(tx_type, 2, 1, None)),
+                Err(ProcessError::ClientMismatch)
+            );
+        }
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(5, 0));
+        assert_eq!(account.held, Decimal::new(0, 0));
+        let account: &ClientAccount = acc_man.accounts.get(&2u16).unwrap();
+        assert_eq!(account.available, Decimal::new(3, 0));
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert!(!account.locked);
+        assert!(!acc_man.transactions[&1u32].is_disputed);
+    }
+
+    #[test]
+    fn dispute_with_existing_account_for_missing_tx() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 2u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert_eq!(
+            acc_man.process_tx(&tx2),
+            Err(ProcessError::TransactionNotFound)
+        );
+    }
+
+    #[test]
+    fn fixed_scale_output_pads_to_precision() {
+        let mut acc_man = AccountManager::with_config(Config {
+            fixed_scale: true,
+            ..Config::default()
+        });
+        let tx = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: 1u16,
+            tx: 1u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx).is_ok());
+        let mut out = Vec::new();
+        acc_man.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client,available,held,total,locked\n1,1.0000,0.0000,1.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn deposit_into_overdrawn_account() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Withdraw),
+            client: client_id,
+            tx: 2u32,
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_ok());
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_ok());
+        assert_eq!(
+            acc_man.accounts.get(&client_id).unwrap().available,
+            Decimal::new(-5, 0)
+        );
+        let tx4 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 4u32,
+            amount: Some(Decimal::new(3, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx4).is_ok());
+
+        let account: &ClientAccount = acc_man.accounts.get(&client_id).unwrap();
+        assert_eq!(account.available, Decimal::new(-2, 0));
+        assert_eq!(account.held, Decimal::new(5, 0));
+        assert_eq!(account.total, Decimal::new(3, 0));
+        assert_eq!(
+            account.total,
+            account.available.amount() + account.held.amount()
+        );
+    }
+
+    #[test]
+    fn from_csv_str_builds_manager() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 10.0\n\
+             deposit, 2, 2, 4.5\n\
+             withdraw, 1, 3, 2.5\n",
+        )
+        .unwrap();
+        assert_eq!(acc_man.accounts.len(), 2);
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(75, 1));
+        assert_eq!(account.total, Decimal::new(75, 1));
+        let account: &ClientAccount = acc_man.accounts.get(&2u16).unwrap();
+        assert_eq!(account.available, Decimal::new(45, 1));
+    }
+
+    #[test]
+    fn dispute_rows_may_omit_the_amount_column() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 10.0\n\
+             deposit, 1, 2, 4.0\n\
+             dispute, 1, 1\n\
+             dispute, 1, 2\n\
+             resolve, 1, 2\n\
+             deposit, 2, 3, 1.0\n\
+             dispute, 2, 3\n\
+             chargeback, 2, 3\n",
+        )
+        .unwrap();
+        assert!(acc_man.rejections().is_empty());
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(4, 0));
+        assert_eq!(account.held, Decimal::new(10, 0));
+        let account: &ClientAccount = acc_man.accounts.get(&2u16).unwrap();
+        assert_eq!(account.total, Decimal::new(0, 0));
+        assert!(account.locked);
+    }
+
+    #[test]
+    fn deposit_to_reserved_client_zero() {
+        let tx = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: 0u16,
+            tx: 1u32,
+            amount: Some(Decimal::new(1, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.process_tx(&tx).is_ok());
+
+        let mut acc_man = AccountManager::with_config(Config {
+            reserve_client_zero: true,
+            ..Config::default()
+        });
+        assert_eq!(acc_man.process_tx(&tx), Err(ProcessError::ReservedClient));
+        assert!(!acc_man.accounts.contains_key(&0u16));
+    }
+
+    #[test]
+    fn dispute_amount_larger_than_original() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(6, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert_eq!(
+            acc_man.process_tx(&tx2),
+            Err(ProcessError::DisputeExceedsOriginal)
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&client_id).unwrap();
+        assert_eq!(account.available, Decimal::new(5, 0));
+        assert_eq!(account.held, Decimal::new(0, 0));
+    }
+
+    #[test]
+    fn partial_dispute_then_resolve() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(2, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_ok());
+        let account: &ClientAccount = acc_man.accounts.get(&client_id).unwrap();
+        assert_eq!(account.available, Decimal::new(3, 0));
+        assert_eq!(account.held, Decimal::new(2, 0));
+        assert_eq!(account.total, Decimal::new(5, 0));
+
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Resolve),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx3).is_ok());
+        let account: &ClientAccount = acc_man.accounts.get(&client_id).unwrap();
+        assert_eq!(account.available, Decimal::new(5, 0));
+        assert_eq!(account.held, Decimal::new(0, 0));
+    }
+
+    #[test]
+    fn rebuild_from_event_log() {
+        let mut acc_man = AccountManager::with_config(Config {
+            record_events: true,
+            ..Config::default()
+        });
+        let csv = "type, client, tx, amount\n\
+                   deposit, 1, 1, 10.0\n\
+                   deposit, 1, 2, 4.0\n\
+                   deposit, 2, 3, 7.0\n\
+                   dispute, 1, 1,\n\
+                   resolve, 1, 1,\n\
+                   dispute, 1, 2,\n\
+                   withdraw, 2, 4, 99.0\n\
+                   dispute, 2, 3,\n\
+                   chargeback, 2, 3,\n";
+        acc_man.process_csv(csv.as_bytes()).unwrap();
+        assert_eq!(acc_man.event_log().len(), 8);
+
+        let rebuilt = AccountManager::from_event_log(acc_man.event_log());
+        let mut expected = Vec::new();
+        acc_man.write_csv(&mut expected).unwrap();
+        let mut actual = Vec::new();
+        rebuilt.write_csv(&mut actual).unwrap();
+        assert_eq!(actual, expected);
+        assert!(!rebuilt.transactions.get(&1u32).unwrap().is_disputed);
+        assert!(rebuilt.transactions.get(&2u32).unwrap().is_disputed);
+        assert!(rebuilt.accounts.get(&2u16).unwrap().locked);
+        assert_eq!(rebuilt.event_log().len(), 8);
+    }
+
+    #[test]
+    fn rebuild_from_event_log_after_resolve_all() {
+        let mut acc_man = AccountManager::with_config(Config {
+            record_events: true,
+            ..Config::default()
+        });
+        acc_man
+            .process_csv(
+                "type, client, tx, amount\n\
+                 deposit, 1, 1, 10.0\n\
+                 deposit, 1, 2, 4.0\n\
+                 dispute, 1, 1,\n\
+                 dispute, 1, 2,\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        assert_eq!(acc_man.resolve_all(1), Ok(2));
+        assert_eq!(acc_man.event_log().len(), 6);
+        assert!(acc_man.verify_against(acc_man.event_log()));
+
+        let rebuilt = AccountManager::from_event_log(acc_man.event_log());
+        assert_eq!(rebuilt.accounts, acc_man.accounts);
+        assert_eq!(rebuilt.disputed_amount(1), Some(Decimal::new(0, 0)));
+    }
+
+    #[test]
+    fn rebuild_from_event_log_with_its_config() {
+        let config = Config {
+            record_events: true,
+            dispute_overhold_policy: DisputeOverholdPolicy::CapAtAvailable,
+            ..Config::default()
+        };
+        let mut acc_man = AccountManager::with_config(config.clone());
+        acc_man
+            .process_csv(
+                "type, client, tx, amount\n\
+                 deposit, 1, 1, 10.0\n\
+                 withdraw, 1, 2, 6.0\n\
+                 dispute, 1, 1,\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+
+        let rebuilt = AccountManager::from_event_log_with_config(acc_man.event_log(), config);
+        assert_eq!(rebuilt.accounts, acc_man.accounts);
+        let account: &ClientAccount = rebuilt.accounts.get(&1u16).unwrap();
+        assert_eq!(account.held, Decimal::new(4, 0));
+
+        let default_rebuild = AccountManager::from_event_log(acc_man.event_log());
+        let account: &ClientAccount = default_rebuild.accounts.get(&1u16).unwrap();
+        assert_eq!(account.held, Decimal::new(10, 0));
+    }
+
+    #[test]
+    fn bom_prefixed_csv_is_parsed() {
+        let mut acc_man = AccountManager::default();
+        let csv = "\u{feff}type, client, tx, amount\ndeposit, 1, 1, 2.5\nwithdraw, 1, 2, 1.0\n";
+        let summary = acc_man.process_csv(csv.as_bytes()).unwrap();
+        assert_eq!(summary.applied, 2);
+        assert!(acc_man.rejections().is_empty());
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(15, 1));
+    }
+
+    struct OneByteReader<'a>(&'a [u8]);
+
+    impl<'a> io::Read for OneByteReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn bom_split_across_reads_is_parsed() {
+        let mut acc_man = AccountManager::default();
+        let csv = "\u{feff}type, client, tx, amount\ndeposit, 1, 1, 2.5\n";
+        let summary = acc_man.process_csv(OneByteReader(csv.as_bytes())).unwrap();
+        assert_eq!(summary.applied, 1);
+        assert!(acc_man.rejections().is_empty());
+    }
+
+    #[test]
+    fn withdraw_cannot_spend_held_funds() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        let tx1 = Transaction {
+            tx_type: Some(TxType::Deposit),
+            client: client_id,
+            tx: 1u32,
+            amount: Some(Decimal::new(10, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx1).is_ok());
+        let tx2 = Transaction {
+            tx_type: Some(TxType::Dispute),
+            client: client_id,
+            tx: 1u32,
+            amount: None,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert!(acc_man.process_tx(&tx2).is_ok());
+        let tx3 = Transaction {
+            tx_type: Some(TxType::Withdraw),
+            client: client_id,
+            tx: 3u32,
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        };
+        assert_eq!(
+            acc_man.process_tx(&tx3),
+            Err(ProcessError::InsufficientFunds)
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&client_id).unwrap();
+        assert_eq!(account.available, Decimal::new(0, 0));
+        assert_eq!(account.held, Decimal::new(10, 0));
+        assert_eq!(account.total, Decimal::new(10, 0));
+    }
+
+    #[test]
+    fn net_position_and_disputed_amount() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 10.0\n\
+             deposit, 1, 2, 2.5\n\
+             dispute, 1, 1,\n",
+        )
+        .unwrap();
+        assert_eq!(acc_man.net_position(1u16), Some(Decimal::new(125, 1)));
+        assert_eq!(acc_man.disputed_amount(1u16), Some(Decimal::new(10, 0)));
+        assert_eq!(acc_man.net_position(2u16), None);
+        assert_eq!(acc_man.disputed_amount(2u16), None);
+
+        acc_man.resolve_all(1u16).unwrap();
+        assert_eq!(acc_man.net_position(1u16), Some(Decimal::new(125, 1)));
+        assert_eq!(acc_man.disputed_amount(1u16), Some(Decimal::new(0, 0)));
+
+        for tx_type in [TxType::Dispute, TxType::Chargeback] {
+            assert!(acc_man
+                .process_tx(&Transaction::new(tx_type, 1, 2, None))
+                .is_ok());
+        }
+        assert_eq!(acc_man.net_position(1u16), Some(Decimal::new(10, 0)));
+        assert_eq!(acc_man.disputed_amount(1u16), Some(Decimal::new(0, 0)));
+    }
+
+    #[test]
+    fn unknown_tx_type_policies() {
+        let csv = "type, client, tx, amount\n\
+                   deposit, 1, 1, 3.0\n\
+                   note, 1, 2,\n\
+                   deposit, 1, 3, 1.0\n";
+
+        let mut acc_man = AccountManager::default();
+        let summary = acc_man.process_csv(csv.as_bytes()).unwrap();
+        assert_eq!(summary.rejected, 1);
+        assert_eq!(
+            acc_man.rejections()[0].reason,
+            ProcessError::UnknownTxType("note".into())
+        );
+
+        let mut acc_man = AccountManager::with_config(Config {
+            unknown_type_policy: UnknownTypePolicy::Skip,
+            ..Config::default()
+        });
+        let summary = acc_man.process_csv(csv.as_bytes()).unwrap();
+        assert_eq!(summary.rejected, 0);
+        assert_eq!(summary.applied, 2);
+        assert_eq!(summary.skipped, 1);
+        assert!(acc_man.rejections().is_empty());
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(4, 0));
+
+        let mut acc_man = AccountManager::with_config(Config {
+            unknown_type_policy: UnknownTypePolicy::Skip,
+            ..Config::default()
+        });
+        let mut out = Vec::new();
+        acc_man.process_streaming(csv.as_bytes(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client,available,held,total,locked\n\
+             1,3,0,3,false\n\
+             1,4,0,4,false\n"
+        );
+    }
+
+    #[test]
+    fn deposit_and_withdraw_helpers_lifecycle() {
+        let mut acc_man = AccountManager::default();
         let client_id = 1u16;
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(1, 0)),
-            is_disputed: false,
+        assert!(acc_man.withdraw(client_id, 1, Decimal::new(1, 0)).is_err());
+        assert!(acc_man.deposit(client_id, 2, Decimal::new(10, 0)).is_ok());
+        assert!(acc_man.deposit(client_id, 3, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.withdraw(client_id, 4, Decimal::new(4, 0)).is_ok());
+        assert_eq!(
+            acc_man.deposit(client_id, 4, Decimal::new(1, 0)),
+            Err(ProcessError::DuplicateTransaction)
+        );
+        let dispute = Transaction::new(TxType::Dispute, client_id, 3, None);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        assert_eq!(
+            acc_man.withdraw(client_id, 5, Decimal::new(7, 0)),
+            Err(ProcessError::InsufficientFunds)
+        );
+        let chargeback = Transaction::new(TxType::Chargeback, client_id, 3, None);
+        assert!(acc_man.process_tx(&chargeback).is_ok());
+        assert_eq!(
+            acc_man.deposit(client_id, 6, Decimal::new(1, 0)),
+            Err(ProcessError::AccountLocked)
+        );
+
+        let account: &ClientAccount = acc_man.accounts.get(&client_id).unwrap();
+        assert_eq!(account.available, Decimal::new(6, 0));
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.total, Decimal::new(6, 0));
+        assert!(account.locked);
+    }
+
+    #[test]
+    fn each_rejection_reports_its_own_variant() {
+        let setup = "type, client, tx, amount\n\
+                     deposit, 1, 1, 10.0\n\
+                     withdraw, 1, 2, 1.0\n\
+                     deposit, 2, 3, 5.0\n\
+                     dispute, 2, 3,\n\
+                     chargeback, 2, 3,\n";
+        let cases = vec![
+            (
+                Transaction::new(TxType::Deposit, 1, 1, Some(Decimal::new(1, 0))),
+                ProcessError::DuplicateTransaction,
+            ),
+            (
+                Transaction::new(TxType::Deposit, 1, 10, None),
+                ProcessError::AmountRequired,
+            ),
+            (
+                Transaction::new(TxType::Deposit, 2, 11, Some(Decimal::new(1, 0))),
+                ProcessError::AccountLocked,
+            ),
+            (
+                Transaction::new(TxType::Withdraw, 1, 12, Some(Decimal::new(100, 0))),
+                ProcessError::InsufficientFunds,
+            ),
+            (
+                Transaction::new(TxType::Dispute, 1, 2, None),
+                ProcessError::NotDisputable,
+            ),
+            (
+                Transaction::new(TxType::Resolve, 1, 1, None),
+                ProcessError::NotDisputed,
+            ),
+            (
+                Transaction::new(TxType::Chargeback, 1, 1, None),
+                ProcessError::NotDisputed,
+            ),
+            (
+                Transaction::new(TxType::Dispute, 1, 99, None),
+                ProcessError::TransactionNotFound,
+            ),
+        ];
+        for (tx, expected) in cases {
+            let mut acc_man = AccountManager::from_csv_str(setup).unwrap();
+            assert!(acc_man.rejections().is_empty());
+            assert_eq!(acc_man.process_tx(&tx), Err(expected), "{:?}", tx);
+        }
+    }
+
+    #[test]
+    fn rejected_withdraw_does_not_use_up_tx_id() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        assert_eq!(
+            acc_man.withdraw(client_id, 1, Decimal::new(1, 0)),
+            Err(ProcessError::NoSuchAccount)
+        );
+        assert!(acc_man.deposit(client_id, 1, Decimal::new(2, 0)).is_ok());
+        assert_eq!(
+            acc_man.withdraw(client_id, 2, Decimal::new(3, 0)),
+            Err(ProcessError::InsufficientFunds)
+        );
+        assert!(acc_man.withdraw(client_id, 2, Decimal::new(1, 0)).is_ok());
+        let account: &ClientAccount = acc_man.accounts.get(&client_id).unwrap();
+        assert_eq!(account.available, Decimal::new(1, 0));
+    }
+
+    #[test]
+    fn account_output_is_not_needlessly_quoted() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(2, 1, Decimal::new(15, 1)).is_ok());
+        assert!(acc_man.deposit(1, 2, Decimal::new(3, 0)).is_ok());
+        let mut out = Vec::new();
+        acc_man.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client,available,held,total,locked\n1,3,0,3,false\n2,1.5,0,1.5,false\n"
+        );
+    }
+
+    #[test]
+    fn diff_reports_differing_client() {
+        let csv = "type, client, tx, amount\n\
+                   deposit, 1, 1, 10.0\n\
+                   deposit, 2, 2, 4.0\n";
+        let ours = AccountManager::from_csv_str(csv).unwrap();
+        let mut theirs = AccountManager::from_csv_str(csv).unwrap();
+        assert!(ours.diff(&theirs).is_empty());
+
+        assert!(theirs.withdraw(1, 3, Decimal::new(25, 1)).is_ok());
+        let diffs = ours.diff(&theirs);
+        assert_eq!(
+            diffs,
+            vec![AccountDiff {
+                client: 1,
+                available: Some((Decimal::new(10, 0), Decimal::new(75, 1))),
+                held: None,
+                total: Some((Decimal::new(10, 0), Decimal::new(75, 1))),
+                locked: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn bounded_store_evicts_oldest_settled_tx() {
+        let mut acc_man = AccountManager::with_config(Config {
+            max_tracked_transactions: Some(2),
+            ..Config::default()
+        });
+        let client_id = 1u16;
+        assert!(acc_man.deposit(client_id, 1, Decimal::new(1, 0)).is_ok());
+        assert!(acc_man.deposit(client_id, 2, Decimal::new(2, 0)).is_ok());
+        let dispute = Transaction::new(TxType::Dispute, client_id, 1, None);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        assert!(acc_man.deposit(client_id, 3, Decimal::new(3, 0)).is_ok());
+
+        assert_eq!(acc_man.transactions.len(), 2);
+        assert!(acc_man.transactions.get(&1u32).unwrap().is_disputed);
+        assert!(!acc_man.transactions.contains_key(&2u32));
+        assert!(acc_man.transactions.contains_key(&3u32));
+        let dispute = Transaction::new(TxType::Dispute, client_id, 2, None);
+        assert_eq!(
+            acc_man.process_tx(&dispute),
+            Err(ProcessError::TransactionNotFound)
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&client_id).unwrap();
+        assert_eq!(account.available, Decimal::new(5, 0));
+        assert_eq!(account.held, Decimal::new(1, 0));
+    }
+
+    #[test]
+    fn disputable_transactions() {
+        let mut acc_man = AccountManager::default();
+        let client_id = 1u16;
+        assert!(acc_man.deposit(client_id, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.withdraw(client_id, 2, Decimal::new(1, 0)).is_ok());
+        assert!(acc_man.is_disputable(1));
+        assert!(!acc_man.is_disputable(2));
+        assert!(!acc_man.is_disputable(99));
+
+        let dispute = Transaction::new(TxType::Dispute, client_id, 1, None);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        assert!(!acc_man.is_disputable(1));
+        assert_eq!(
+            acc_man.process_tx(&dispute),
+            Err(ProcessError::AlreadyDisputed)
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&client_id).unwrap();
+        assert_eq!(account.held, Decimal::new(5, 0));
+    }
+
+    #[test]
+    fn errors_csv_lists_rejected_rows() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 5.0\n\
+             withdraw, 1, 2, 9.0\n\
+             deposit, 1\n\
+             dispute, 1, 7,\n",
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        acc_man.write_errors_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "row,tx,client,type,reason\n\
+             3,2,1,withdraw,Insufficient Funds\n\
+             4,,,,\"Malformed Row: expected at least 3 fields, found 2\"\n\
+             5,7,1,dispute,No Associated Transaction could be Found\n"
+        );
+    }
+
+    #[test]
+    fn client_id_above_u16_is_rejected() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 65536, 1, 5.0\n\
+             deposit, 65535, 2, 5.0\n",
+        )
+        .unwrap();
+        let rejections = acc_man.rejections();
+        assert_eq!(rejections.len(), 1);
+        assert_eq!(rejections[0].row, 2);
+        assert_eq!(
+            rejections[0].reason,
+            ProcessError::ClientIdOutOfRange("65536".into())
+        );
+        assert!(!acc_man.accounts.contains_key(&0u16));
+        assert!(acc_man.accounts.contains_key(&65535u16));
+    }
+
+    #[test]
+    fn verify_against_detects_tampering() {
+        let mut acc_man = AccountManager::with_config(Config {
+            record_events: true,
+            ..Config::default()
+        });
+        acc_man
+            .process_csv(
+                "type, client, tx, amount\n\
+                 deposit, 1, 1, 10.0\n\
+                 deposit, 2, 2, 3.0\n\
+                 dispute, 1, 1,\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        let events = acc_man.event_log().to_vec();
+        assert!(acc_man.verify_against(&events));
+
+        let account = acc_man.accounts.get_mut(&2u16).unwrap();
+        account.available = Money::new(account.available.amount() + Decimal::new(1, 0));
+        assert!(!acc_man.verify_against(&events));
+    }
+
+    #[test]
+    fn pending_withdrawal_then_settle() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 10.0\n\
+             pending, 1, 2, 4.0\n",
+        )
+        .unwrap();
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(6, 0));
+        assert_eq!(account.pending, Decimal::new(4, 0));
+        assert_eq!(account.total, Decimal::new(10, 0));
+
+        assert!(acc_man
+            .process_tx(&Transaction::new(TxType::Settle, 1, 2, None))
+            .is_ok());
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(6, 0));
+        assert_eq!(account.pending, Decimal::new(0, 0));
+        assert_eq!(account.total, Decimal::new(6, 0));
+        assert_eq!(
+            acc_man.process_tx(&Transaction::new(TxType::Cancel, 1, 2, None)),
+            Err(ProcessError::NotPending)
+        );
+    }
+
+    #[test]
+    fn pending_withdrawal_then_cancel() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 10.0\n\
+             pending, 1, 2, 3.0\n",
+        )
+        .unwrap();
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(7, 0));
+        assert_eq!(account.pending, Decimal::new(3, 0));
+        assert_eq!(account.total, Decimal::new(10, 0));
+        assert_eq!(
+            acc_man.withdraw(1, 3, Decimal::new(8, 0)),
+            Err(ProcessError::InsufficientFunds)
+        );
+
+        assert!(acc_man
+            .process_tx(&Transaction::new(TxType::Cancel, 1, 2, None))
+            .is_ok());
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(10, 0));
+        assert_eq!(account.pending, Decimal::new(0, 0));
+        assert_eq!(account.total, Decimal::new(10, 0));
+        assert_eq!(
+            acc_man.process_tx(&Transaction::new(TxType::Settle, 1, 2, None)),
+            Err(ProcessError::NotPending)
+        );
+    }
+
+    #[test]
+    fn settlement_by_another_client_is_rejected() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 10.0\n\
+             deposit, 2, 2, 5.0\n\
+             pending, 1, 3, 3.0\n",
+        )
+        .unwrap();
+        let before = acc_man.snapshot_accounts();
+        for tx_type in [TxType::Settle, TxType::Cancel] {
+            assert_eq!(
+                acc_man.process_tx(&Transaction::new(tx_type, 2, 3, None)),
+                Err(ProcessError::ClientMismatch)
+            );
+        }
+        assert_eq!(acc_man.snapshot_accounts(), before);
+        assert!(acc_man
+            .process_tx(&Transaction::new(TxType::Settle, 1, 3, None))
+            .is_ok());
+        assert_eq!(acc_man.accounts[&1u16].total, Decimal::new(7, 0));
+    }
+
+    #[test]
+    fn dispute_of_spent_funds_is_refused() {
+        let mut acc_man = AccountManager::with_config(Config {
+            require_funds_for_dispute: true,
+            dispute_overhold_policy: DisputeOverholdPolicy::CapAtAvailable,
+            ..Config::default()
+        });
+        acc_man
+            .process_csv(
+                "type, client, tx, amount\n\
+                 deposit, 1, 1, 10.0\n\
+                 withdraw, 1, 2, 10.0\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        assert_eq!(
+            acc_man.process_tx(&Transaction::new(TxType::Dispute, 1, 1, None)),
+            Err(ProcessError::DisputeExceedsAvailable)
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert!(!acc_man.transactions[&1u32].is_disputed);
+    }
+
+    #[test]
+    fn dispute_overhold_policies() {
+        let csv = "type, client, tx, amount\n\
+                   deposit, 1, 1, 10.0\n\
+                   withdraw, 1, 2, 6.0\n";
+        let dispute = Transaction::new(TxType::Dispute, 1, 1, None);
+        let expectations = vec![
+            (DisputeOverholdPolicy::Allow, Ok(()), -6, 10),
+            (DisputeOverholdPolicy::CapAtAvailable, Ok(()), 0, 4),
+            (
+                DisputeOverholdPolicy::Reject,
+                Err(ProcessError::DisputeExceedsAvailable),
+                4,
+                0,
+            ),
+        ];
+        for (policy, result, available, held) in expectations {
+            let mut acc_man = AccountManager::with_config(Config {
+                dispute_overhold_policy: policy,
+                ..Config::default()
+            });
+            acc_man.process_csv(csv.as_bytes()).unwrap();
+            assert_eq!(acc_man.process_tx(&dispute), result, "{:?}", policy);
+            let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+            assert_eq!(account.available, Decimal::new(available, 0));
+            assert_eq!(account.held, Decimal::new(held, 0));
+            assert_eq!(account.total, Decimal::new(4, 0));
+        }
+    }
+
+    #[test]
+    fn disputable_amount_tracks_dispute_state() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.deposit(1, 2, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.withdraw(1, 3, Decimal::new(1, 0)).is_ok());
+        assert_eq!(acc_man.disputable_amount(1), Some(Decimal::new(5, 0)));
+        assert_eq!(acc_man.disputable_amount(3), None);
+        assert_eq!(acc_man.disputable_amount(99), None);
+
+        let dispute = Transaction::new(TxType::Dispute, 1, 1, None);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        assert_eq!(acc_man.disputable_amount(1), Some(Decimal::new(0, 0)));
+
+        let partial = Transaction::new(TxType::Dispute, 1, 2, Some(Decimal::new(2, 0)));
+        assert!(acc_man.process_tx(&partial).is_ok());
+        assert_eq!(acc_man.disputable_amount(2), Some(Decimal::new(0, 0)));
+        let rest = Transaction::new(TxType::Dispute, 1, 2, Some(Decimal::new(3, 0)));
+        assert_eq!(
+            acc_man.process_tx(&rest),
+            Err(ProcessError::AlreadyDisputed)
+        );
+    }
+
+    #[test]
+    fn chargeback_rejected_when_held_is_short() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        let dispute = Transaction::new(TxType::Dispute, 1, 1, None);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        acc_man.accounts.get_mut(&1u16).unwrap().held = Money::new(Decimal::new(2, 0));
+
+        let chargeback = Transaction::new(TxType::Chargeback, 1, 1, None);
+        assert_eq!(
+            acc_man.process_tx(&chargeback),
+            Err(ProcessError::HeldUnderflow)
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.held, Decimal::new(2, 0));
+        assert!(!account.locked);
+        assert!(acc_man.chargebacks().is_empty());
+    }
+
+    #[test]
+    fn process_records_reports_each_failure() {
+        let mut acc_man = AccountManager::default();
+        let records = vec![
+            Transaction::new(TxType::Deposit, 1, 1, Some(Decimal::new(5, 0))),
+            Transaction::new(TxType::Deposit, 1, 2, Some(Decimal::new(3, 0))),
+            Transaction::new(TxType::Deposit, 1, 1, Some(Decimal::new(7, 0))),
+        ];
+        let mut failures = Vec::new();
+        acc_man.process_records(records, |tx, e| failures.push((tx.tx, e)));
+        assert_eq!(failures, vec![(1u32, ProcessError::DuplicateTransaction)]);
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(8, 0));
+    }
+
+    #[test]
+    fn balance_trace_follows_each_deposit() {
+        let mut acc_man = AccountManager::with_config(Config {
+            trace_balances: true,
+            ..Config::default()
+        });
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.deposit(2, 2, Decimal::new(3, 0)).is_ok());
+        assert!(acc_man.deposit(1, 3, Decimal::new(2, 0)).is_ok());
+        assert!(acc_man.deposit(1, 3, Decimal::new(2, 0)).is_err());
+
+        let point = |tx, client, available| BalancePoint {
+            tx,
+            client,
+            available: Decimal::new(available, 0),
+            held: Decimal::new(0, 0),
         };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx2 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 2u32,
-            amount: Some(Decimal::new(1, 0)),
-            is_disputed: false,
+        assert_eq!(
+            acc_man.balance_trace(),
+            &[point(1, 1, 5), point(2, 2, 3), point(3, 1, 7)]
+        );
+    }
+
+    #[test]
+    fn debug_checks_catch_a_corrupted_total() {
+        let mut acc_man = AccountManager::with_config(Config {
+            debug_checks: true,
+            ..Config::default()
+        });
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        // Stands in for a bug: a dispute only moves funds, so leaves the bad total in place.
+        acc_man.accounts.get_mut(&1u16).unwrap().total = Money::new(Decimal::new(6, 0));
+        assert_eq!(
+            acc_man.process_tx(&Transaction::new(TxType::Dispute, 1, 1, None)),
+            Err(ProcessError::InvariantViolation(
+                "client 1 total is 6 but available 0 + held 5 + pending 0 is 5".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn client_stats_counts_one_clients_transactions() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 10.0\n\
+             deposit, 1, 2, 5.0\n\
+             withdraw, 1, 3, 2.5\n\
+             deposit, 2, 4, 100.0\n\
+             deposit, 1, 5, 1.0\n\
+             dispute, 1, 2,\n\
+             dispute, 1, 5,\n\
+             resolve, 1, 5,\n\
+             dispute, 2, 4,\n\
+             chargeback, 2, 4,\n",
+        )
+        .unwrap();
+        assert_eq!(
+            acc_man.client_stats(1),
+            Ok(ClientStats {
+                deposits: 3,
+                withdrawals: 1,
+                disputed: 1,
+                volume: Decimal::new(185, 1),
+            })
+        );
+        let stats = acc_man.client_stats(2).unwrap();
+        assert_eq!(stats.deposits, 1);
+        assert_eq!(stats.disputed, 0);
+        assert_eq!(acc_man.client_stats(3), Err(ProcessError::NoSuchAccount));
+
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::MAX).is_ok());
+        assert!(acc_man.withdraw(1, 2, Decimal::new(1, 0)).is_ok());
+        assert_eq!(acc_man.client_stats(1), Err(ProcessError::Overflow));
+    }
+
+    #[test]
+    fn top_accounts_by_total() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 5.0\n\
+             deposit, 2, 2, 20.0\n\
+             deposit, 3, 3, 12.0\n\
+             withdraw, 2, 4, 10.0\n\
+             deposit, 4, 5, 5.0\n",
+        )
+        .unwrap();
+        assert_eq!(acc_man.top_account_by_total().unwrap().client, 3);
+        let top: Vec<u16> = acc_man.top_n_by_total(3).iter().map(|a| a.client).collect();
+        assert_eq!(top, [3, 2, 1]);
+        assert_eq!(acc_man.top_n_by_total(10).len(), 4);
+        assert_eq!(AccountManager::default().top_account_by_total(), None);
+    }
+
+    #[test]
+    fn transactions_grouped_by_client() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 7, 1.0\n\
+             deposit, 2, 2, 4.0\n\
+             deposit, 1, 3, 2.0\n\
+             withdraw, 1, 5, 1.0\n\
+             dispute, 2, 2,\n",
+        )
+        .unwrap();
+        let by_client = acc_man.transactions_by_client();
+        assert_eq!(by_client.len(), 2);
+        let ids = |client| {
+            by_client[&client]
+                .iter()
+                .map(|t| t.tx)
+                .collect::<Vec<u32>>()
         };
-        assert!(acc_man.process_tx(&tx2).is_ok());
+        assert_eq!(ids(1), [3, 5, 7]);
+        assert_eq!(ids(2), [2]);
+    }
+
+    #[test]
+    fn deposit_count_per_client() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 1.5\n\
+             deposit, 2, 2, 4.0\n\
+             deposit, 1, 3, 2.0\n\
+             withdraw, 1, 4, 1.0\n\
+             deposit, 1, 5, 0.5\n",
+        )
+        .unwrap();
+        assert_eq!(acc_man.deposit_count(1), 3);
+        assert_eq!(acc_man.deposit_count(2), 1);
+        assert_eq!(acc_man.deposit_count(3), 0);
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(3, 0));
+    }
+
+    #[test]
+    fn resolve_rejected_when_held_is_short() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        let dispute = Transaction::new(TxType::Dispute, 1, 1, None);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        acc_man.accounts.get_mut(&1u16).unwrap().held = Money::new(Decimal::new(2, 0));
+
+        let resolve = Transaction::new(TxType::Resolve, 1, 1, None);
+        assert_eq!(
+            acc_man.process_tx(&resolve),
+            Err(ProcessError::HeldUnderflow)
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(0, 0));
+        assert_eq!(account.held, Decimal::new(2, 0));
+        assert!(!acc_man.is_disputable(1));
+    }
+
+    #[test]
+    fn extended_output_schema_headers() {
+        let mut acc_man = AccountManager::with_config(Config {
+            output_schema: OutputSchema::Extended,
+            ..Config::default()
+        });
+        assert!(acc_man.deposit(1, 1, Decimal::new(15, 1)).is_ok());
+        let mut out = Vec::new();
+        acc_man.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client_id,available_balance,held_balance,total_balance,is_locked,label\n\
+             1,1.5,0,1.5,false,\n"
+        );
+    }
+
+    #[test]
+    fn batch_is_applied_in_timestamp_order() {
+        let at = |secs: i64| Utc.timestamp_opt(1_600_000_000 + secs, 0).unwrap();
+        let batch = vec![
+            Transaction::new(TxType::Withdraw, 1, 2, Some(Decimal::new(3, 0)))
+                .with_timestamp(at(20)),
+            Transaction::new(TxType::Dispute, 1, 1, None).with_timestamp(at(30)),
+            Transaction::new(TxType::Deposit, 1, 1, Some(Decimal::new(5, 0)))
+                .with_timestamp(at(10)),
+        ];
+        let mut acc_man = AccountManager::with_config(Config {
+            record_events: true,
+            ..Config::default()
+        });
+        let mut failures = 0;
+        acc_man.process_batch(batch, |_, _| failures += 1);
+        assert_eq!(failures, 0);
+        let applied: Vec<u32> = acc_man.event_log().iter().map(|t| t.tx).collect();
+        assert_eq!(applied, vec![1, 2, 1]);
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(-3, 0));
+        assert_eq!(account.held, Decimal::new(5, 0));
+    }
+
+    #[test]
+    fn timestamp_column_is_parsed() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount, timestamp\n\
+             deposit, 1, 1, 2.0, 2021-03-01T12:00:00Z\n\
+             deposit, 1, 2, 1.0,\n",
+        )
+        .unwrap();
+        assert!(acc_man.rejections().is_empty());
+        let stored = acc_man.transactions.get(&1u32).unwrap();
+        assert_eq!(
+            stored.timestamp,
+            Some(Utc.with_ymd_and_hms(2021, 3, 1, 12, 0, 0).unwrap())
+        );
+        assert_eq!(acc_man.transactions.get(&2u32).unwrap().timestamp, None);
+    }
+
+    #[test]
+    fn deposit_overflow_leaves_account_unchanged() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::MAX).is_ok());
+        assert_eq!(
+            acc_man.deposit(1, 2, Decimal::new(1, 0)),
+            Err(ProcessError::Overflow)
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::MAX);
+        assert_eq!(account.total, Decimal::MAX);
+        assert!(!acc_man.is_disputable(2));
+    }
+
+    #[test]
+    fn stats_overflow_across_accounts() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::MAX).is_ok());
+        assert!(acc_man.deposit(2, 2, Decimal::MAX).is_ok());
+        assert_eq!(acc_man.stats(), Err(ProcessError::Overflow));
+    }
+
+    #[test]
+    fn only_changed_accounts_since_snapshot() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.deposit(2, 2, Decimal::new(5, 0)).is_ok());
+        let snapshot = acc_man.snapshot();
+        assert!(acc_man.accounts_changed_since(&snapshot).is_empty());
+
+        assert!(acc_man.deposit(2, 3, Decimal::new(1, 0)).is_ok());
+        let changed: Vec<u16> = acc_man
+            .accounts_changed_since(&snapshot)
+            .iter()
+            .map(|acc| acc.client())
+            .collect();
+        assert_eq!(changed, vec![2]);
+    }
+
+    #[test]
+    fn atomic_group_is_rolled_back_on_failure() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        let group = vec![
+            Transaction::new(TxType::Deposit, 2, 2, Some(Decimal::new(3, 0))),
+            Transaction::new(TxType::Withdraw, 1, 3, Some(Decimal::new(9, 0))),
+            Transaction::new(TxType::Dispute, 1, 1, None),
+        ];
+        assert_eq!(
+            acc_man.process_atomic(&group),
+            Err(ProcessError::InsufficientFunds)
+        );
+        assert!(!acc_man.accounts.contains_key(&2u16));
+        assert!(!acc_man.transactions.contains_key(&2u32));
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(5, 0));
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert!(acc_man.is_disputable(1));
+
+        let group = vec![
+            Transaction::new(TxType::Deposit, 2, 2, Some(Decimal::new(3, 0))),
+            Transaction::new(TxType::Withdraw, 1, 3, Some(Decimal::new(4, 0))),
+            Transaction::new(TxType::Dispute, 2, 2, None),
+        ];
+        assert!(acc_man.process_atomic(&group).is_ok());
+        let account: &ClientAccount = acc_man.accounts.get(&2u16).unwrap();
+        assert_eq!(account.held, Decimal::new(3, 0));
+    }
+
+    #[test]
+    fn rolled_back_dispute_leaves_no_held_alert() {
+        let mut acc_man = AccountManager::with_config(Config {
+            held_alert_threshold: Some(Decimal::new(4, 0)),
+            ..Config::default()
+        });
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        let group = vec![
+            Transaction::new(TxType::Dispute, 1, 1, None),
+            Transaction::new(TxType::Withdraw, 1, 2, Some(Decimal::new(9, 0))),
+        ];
+        assert_eq!(
+            acc_man.process_atomic(&group),
+            Err(ProcessError::InsufficientFunds)
+        );
+        assert!(acc_man.held_alerts().is_empty());
+        assert_eq!(acc_man.accounts[&1u16].held, Decimal::new(0, 0));
+    }
+
+    #[test]
+    fn rolled_back_group_restores_dispute_times_and_violations() {
+        let mut acc_man = AccountManager::with_config(Config {
+            monitor_locked_accounts: true,
+            ..Config::default()
+        });
+        let at = Utc.with_ymd_and_hms(2021, 3, 1, 12, 0, 0).unwrap();
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.deposit(1, 2, Decimal::new(3, 0)).is_ok());
+        let mut dispute = Transaction::new(TxType::Dispute, 1, 2, None);
+        dispute.timestamp = Some(at);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+
+        let mut dispute = Transaction::new(TxType::Dispute, 1, 1, None);
+        dispute.timestamp = Some(at);
+        let group = vec![
+            dispute,
+            Transaction::new(TxType::Resolve, 1, 2, None),
+            Transaction::new(TxType::Withdraw, 1, 3, Some(Decimal::new(9, 0))),
+        ];
+        assert_eq!(
+            acc_man.process_atomic(&group),
+            Err(ProcessError::InsufficientFunds)
+        );
+        assert!(!acc_man.dispute_times.contains_key(&1u32));
+        assert_eq!(acc_man.dispute_times.get(&2u32), Some(&at));
+
+        assert!(acc_man
+            .process_tx(&Transaction::new(TxType::Freeze, 1, 0, None))
+            .is_ok());
+        let group = vec![Transaction::new(
+            TxType::Deposit,
+            1,
+            4,
+            Some(Decimal::new(1, 0)),
+        )];
+        assert_eq!(
+            acc_man.process_atomic(&group),
+            Err(ProcessError::AccountFrozen)
+        );
+        assert!(acc_man.locked_violation_attempts().is_empty());
+    }
+
+    #[test]
+    fn process_file_records_run_stats() {
+        let path = std::env::temp_dir().join(format!("run_stats_{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 1.0\n\
+             deposit, 2, 2, 2.0\n\
+             withdraw, 1, 3, 5.0\n",
+        )
+        .unwrap();
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.last_run_stats().is_none());
+        let summary = acc_man.process_file(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let stats = acc_man.last_run_stats().unwrap();
+        assert_eq!(stats.rows, 3);
+        assert_eq!(stats.rows, summary.rows);
+        assert!(stats.rows_per_sec() >= 0.0);
+    }
+
+    #[test]
+    fn repeated_resolve_under_both_modes() {
+        for idempotent in [false, true] {
+            let mut acc_man = AccountManager::with_config(Config {
+                idempotent_resolves: idempotent,
+                ..Config::default()
+            });
+            assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+            let dispute = Transaction::new(TxType::Dispute, 1, 1, None);
+            let resolve = Transaction::new(TxType::Resolve, 1, 1, None);
+            assert!(acc_man.process_tx(&dispute).is_ok());
+            assert!(acc_man.process_tx(&resolve).is_ok());
+            let second = acc_man.process_tx(&resolve);
+            if idempotent {
+                assert_eq!(second, Ok(()));
+            } else {
+                assert_eq!(second, Err(ProcessError::NotDisputed));
+            }
+            let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+            assert_eq!(account.available, Decimal::new(5, 0));
+            assert_eq!(account.held, Decimal::new(0, 0));
+        }
+    }
+
+    #[test]
+    fn transactions_filtered_by_type() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.withdraw(1, 2, Decimal::new(1, 0)).is_ok());
+        assert!(acc_man.deposit(2, 3, Decimal::new(4, 0)).is_ok());
+        assert!(acc_man.withdraw(2, 4, Decimal::new(2, 0)).is_ok());
+        assert!(acc_man.deposit(1, 5, Decimal::new(3, 0)).is_ok());
+
+        let mut deposits: Vec<u32> = acc_man
+            .transactions_of_type(TxType::Deposit)
+            .map(|t| t.tx())
+            .collect();
+        deposits.sort_unstable();
+        assert_eq!(deposits, vec![1, 3, 5]);
+        let mut withdrawals: Vec<u32> = acc_man
+            .transactions_of_type(TxType::Withdraw)
+            .map(|t| t.tx())
+            .collect();
+        withdrawals.sort_unstable();
+        assert_eq!(withdrawals, vec![2, 4]);
+        assert_eq!(acc_man.transactions_of_type(TxType::Pending).count(), 0);
+    }
+
+    #[test]
+    fn clamped_output_flags_overdrawn_accounts() {
+        let mut acc_man = AccountManager::with_config(Config {
+            clamp_negative: true,
+            ..Config::default()
+        });
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.withdraw(1, 2, Decimal::new(4, 0)).is_ok());
+        assert!(acc_man.deposit(2, 3, Decimal::new(2, 0)).is_ok());
+        let dispute = Transaction::new(TxType::Dispute, 1, 1, None);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+
+        let mut out = Vec::new();
+        acc_man.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client,available,held,total,locked,overdrawn\n\
+             1,0,5,1,false,true\n\
+             2,2,0,2,false,false\n"
+        );
+    }
+
+    #[test]
+    fn strict_spec_counts_withdrawal_disputes() {
+        let csv = "type, client, tx, amount\n\
+                   deposit, 1, 1, 5.0\n\
+                   withdraw, 1, 2, 1.0\n\
+                   dispute, 1, 2,\n\
+                   dispute, 1, 99,\n\
+                   dispute, 1, 2,\n";
+        let mut strict = AccountManager::with_config(Config {
+            strict_spec: true,
+            ..Config::default()
+        });
+        let summary = strict.process_csv(csv.as_bytes()).unwrap();
+        assert_eq!(summary.rejected, 3);
+        assert_eq!(summary.withdrawal_disputes_rejected, 2);
+        assert_eq!(strict.rejections()[0].reason, ProcessError::NotDisputable);
+
+        let mut lenient = AccountManager::default();
+        let summary = lenient.process_csv(csv.as_bytes()).unwrap();
+        assert_eq!(summary.rejected, 3);
+        assert_eq!(summary.withdrawal_disputes_rejected, 0);
+    }
+
+    #[test]
+    fn limited_processing_stops_after_max_rows() {
+        let mut csv = String::from("type, client, tx, amount\n");
+        for tx in 1..=10 {
+            csv.push_str(&format!("deposit, 1, {}, 1.0\n", tx));
+        }
+        let mut acc_man = AccountManager::default();
+        let summary = acc_man.process_csv_limited(csv.as_bytes(), 3).unwrap();
+        assert_eq!(summary.rows, 3);
+        assert_eq!(summary.applied, 3);
+        assert_eq!(acc_man.deposit_count(1), 3);
+        assert!(!acc_man.transactions.contains_key(&4u32));
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(3, 0));
+    }
+
+    #[test]
+    fn accounts_compare_and_sort_by_client() {
+        let account = |client: u16, available: i64| ClientAccount {
+            client,
+            available: Money::new(Decimal::new(available, 0)),
+            held: Money::default(),
+            pending: Money::default(),
+            total: Money::new(Decimal::new(available, 0)),
+            locked: false,
+            frozen: false,
+            label: None,
+            currency: None,
+        };
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(2, 1, Decimal::new(5, 0)).is_ok());
+        assert_eq!(acc_man.accounts.get(&2u16), Some(&account(2, 5)));
+        assert_ne!(account(2, 5), account(2, 6));
+
+        let mut accounts = [account(3, 1), account(1, 9), account(2, 5)];
+        accounts.sort();
+        let clients: Vec<u16> = accounts.iter().map(|acc| acc.client()).collect();
+        assert_eq!(clients, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn rejection_summary_counts_each_reason() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 5.0\n\
+             deposit, 1, 1, 5.0\n\
+             withdraw, 1, 2, 9.0\n\
+             deposit, 1, 1, 2.0\n\
+             dispute, 1, 7,\n\
+             withdraw, 1, 3, 8.0\n",
+        )
+        .unwrap();
+        assert_eq!(acc_man.rejections().len(), 5);
+        let mut out = Vec::new();
+        acc_man.rejection_summary_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "reason,count\n\
+             Duplicate Transaction,2\n\
+             Insufficient Funds,2\n\
+             No Associated Transaction could be Found,1\n"
+        );
+    }
+
+    #[test]
+    fn freeze_blocks_deposits_until_unfrozen() {
+        let mut acc_man = AccountManager::with_config(Config {
+            record_events: true,
+            ..Config::default()
+        });
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        let freeze = Transaction::new(TxType::Freeze, 1, 0, None);
+        assert!(acc_man.process_tx(&freeze).is_ok());
+        assert_eq!(
+            acc_man.deposit(1, 2, Decimal::new(1, 0)),
+            Err(ProcessError::AccountFrozen)
+        );
+        assert_eq!(
+            acc_man.withdraw(1, 3, Decimal::new(1, 0)),
+            Err(ProcessError::AccountFrozen)
+        );
+        let mut out = Vec::new();
+        acc_man.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client,available,held,total,locked\n1,5,0,5,true\n"
+        );
+
+        let unfreeze = Transaction::new(TxType::Unfreeze, 1, 0, None);
+        assert!(acc_man.process_tx(&unfreeze).is_ok());
+        assert!(acc_man.deposit(1, 2, Decimal::new(1, 0)).is_ok());
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(6, 0));
+        assert!(!account.locked());
+        let logged: Vec<Option<&TxType>> =
+            acc_man.event_log().iter().map(|t| t.tx_type()).collect();
+        assert_eq!(
+            logged,
+            vec![
+                Some(&TxType::Deposit),
+                Some(&TxType::Freeze),
+                Some(&TxType::Unfreeze),
+                Some(&TxType::Deposit)
+            ]
+        );
+    }
+
+    #[test]
+    fn unfreeze_does_not_lift_a_chargeback_lock() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.deposit(1, 2, Decimal::new(1, 0)).is_ok());
+        for tx_type in [TxType::Dispute, TxType::Chargeback, TxType::Unfreeze] {
+            assert!(acc_man
+                .process_tx(&Transaction::new(tx_type, 1, 2, None))
+                .is_ok());
+        }
+        assert_eq!(
+            acc_man.deposit(1, 3, Decimal::new(1, 0)),
+            Err(ProcessError::AccountLocked)
+        );
+    }
+
+    #[test]
+    fn simultaneous_transactions_apply_in_tx_id_order() {
+        let at = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        let batch = vec![
+            Transaction::new(TxType::Withdraw, 1, 2, Some(Decimal::new(3, 0))).with_timestamp(at),
+            Transaction::new(TxType::Deposit, 1, 1, Some(Decimal::new(5, 0))).with_timestamp(at),
+        ];
+        let mut acc_man = AccountManager::default();
+        let mut failures = 0;
+        acc_man.process_batch(batch, |_, _| failures += 1);
+        assert_eq!(failures, 0);
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(2, 0));
+    }
+
+    #[test]
+    fn projected_available_matches_pre_dispute_available() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.deposit(1, 2, Decimal::new(3, 0)).is_ok());
+        let before = acc_man.accounts.get(&1u16).unwrap().available().amount();
+        let dispute = Transaction::new(TxType::Dispute, 1, 2, None);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        assert_eq!(acc_man.projected_available_if_resolved(1), Some(before));
+        assert_eq!(acc_man.projected_available_if_resolved(2), None);
+    }
+
+    #[test]
+    fn fixed_width_block_is_processed() {
+        let block = "deposit       1         1    10.0000\n\
+                     deposit       2         2     3.5000\n\
+                     \n\
+                     withdraw      1         3     4.0000\n\
+                     withdraw      2         4     9.0000\n\
+                     dispute       1         1\n";
+        let layout = FixedLayout {
+            tx_type: 0..10,
+            client: 10..15,
+            tx: 15..25,
+            amount: 25..40,
+        };
+        let mut acc_man = AccountManager::default();
+        let summary = acc_man
+            .process_fixed_width(block.as_bytes(), layout)
+            .unwrap();
+        assert_eq!(summary.rows, 5);
+        assert_eq!(summary.applied, 4);
+        assert_eq!(acc_man.rejections()[0].row, 5);
+        assert_eq!(
+            acc_man.rejections()[0].reason,
+            ProcessError::InsufficientFunds
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(-4, 0));
+        assert_eq!(account.held, Decimal::new(10, 0));
+        let account: &ClientAccount = acc_man.accounts.get(&2u16).unwrap();
+        assert_eq!(account.available, Decimal::new(35, 1));
+    }
+
+    #[test]
+    fn failing_writer_returns_an_error() {
+        struct FailingWriter;
+        impl io::Write for FailingWriter {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Err(io::Error::other("disk full"))
+            }
+        }
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 1.0\n\
+             deposit, 1, 1, 1.0\n",
+        )
+        .unwrap();
+        assert!(acc_man.write_csv(FailingWriter).is_err());
+        assert!(acc_man.write_errors_csv(FailingWriter).is_err());
+        assert!(acc_man.rejection_summary_csv(FailingWriter).is_err());
+        assert_eq!(
+            acc_man.to_string(),
+            "client,available,held,total,locked\n1,1,0,1,false\n"
+        );
+    }
+
+    #[test]
+    fn tagged_label_appears_in_extended_output() {
+        let mut acc_man = AccountManager::with_config(Config {
+            output_schema: OutputSchema::Extended,
+            ..Config::default()
+        });
+        assert!(acc_man.deposit(1, 1, Decimal::new(2, 0)).is_ok());
+        assert!(acc_man.deposit(2, 2, Decimal::new(3, 0)).is_ok());
+        assert!(acc_man.tag(1, "vip, flagged").is_ok());
+        assert_eq!(acc_man.tag(3, "vip"), Err(ProcessError::NoSuchAccount));
+        assert_eq!(
+            acc_man.accounts.get(&1u16).unwrap().label(),
+            Some("vip, flagged")
+        );
+
+        let mut out = Vec::new();
+        acc_man.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client_id,available_balance,held_balance,total_balance,is_locked,label\n\
+             1,2,0,2,false,\"vip, flagged\"\n\
+             2,3,0,3,false,\n"
+        );
+    }
+
+    #[test]
+    fn accounts_in_available_range() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.deposit(2, 2, Decimal::new(10, 0)).is_ok());
+        assert!(acc_man.deposit(3, 3, Decimal::new(20, 0)).is_ok());
+        assert_eq!(
+            acc_man.accounts_with_available_between(Decimal::new(5, 0), Decimal::new(10, 0)),
+            vec![1, 2]
+        );
+        assert_eq!(
+            acc_man.accounts_with_available_between(Decimal::new(11, 0), Decimal::new(19, 0)),
+            Vec::<u16>::new()
+        );
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn binary_round_trip_keeps_state() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount, timestamp\n\
+             deposit, 1, 1, 5.1234, 2021-03-01T12:00:00Z\n\
+             deposit, 1, 2, 3.0\n\
+             dispute, 1, 2, 1.0\n\
+             deposit, 2, 3, 7.0\n\
+             pending, 2, 4, 2.0\n\
+             deposit, 3, 5, 4.0\n\
+             dispute, 3, 5,\n\
+             chargeback, 3, 5,\n",
+        )
+        .unwrap();
+        assert!(acc_man.tag(2, "vip").is_ok());
+
+        let restored = AccountManager::from_bytes(&acc_man.to_bytes()).unwrap();
+        assert!(restored.diff(&acc_man).is_empty());
+        assert_eq!(restored.accounts, acc_man.accounts);
+        assert_eq!(restored.disputed_amount(1), Some(Decimal::new(1, 0)));
+        // The charged back transaction is not counted as an open dispute.
+        assert_eq!(restored.disputed_amount(3), Some(Decimal::new(0, 0)));
+        assert_eq!(restored.stats().unwrap().disputed_count, 1);
+        assert!(restored.transactions[&5u32].charged_back);
+        assert_eq!(
+            restored.transactions.get(&1u32).unwrap().timestamp,
+            acc_man.transactions.get(&1u32).unwrap().timestamp
+        );
+        assert_eq!(restored.transactions.len(), acc_man.transactions.len());
+        let mut restored = restored;
+        let settle = Transaction::new(TxType::Settle, 2, 4, None);
+        assert!(restored.process_tx(&settle).is_ok());
+        assert!(acc_man.process_tx(&settle).is_ok());
+        assert_eq!(restored.accounts, acc_man.accounts);
+
+        assert!(AccountManager::from_bytes(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn withdrawal_dispute_lifecycle() {
+        let mut acc_man = AccountManager::with_config(Config {
+            withdrawal_disputes: true,
+            ..Config::default()
+        });
+        assert!(acc_man.deposit(1, 1, Decimal::new(10, 0)).is_ok());
+        assert!(acc_man.withdraw(1, 2, Decimal::new(4, 0)).is_ok());
+        assert!(acc_man.is_disputable(2));
+        let balances = |acc_man: &AccountManager| {
+            let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+            (
+                account.available,
+                account.held,
+                account.total,
+                account.locked,
+            )
+        };
+        let d = |v| Money::new(Decimal::new(v, 0));
+
+        let dispute = Transaction::new(TxType::Dispute, 1, 2, None);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        assert_eq!(balances(&acc_man), (d(6), d(4), d(10), false));
+
+        let resolve = Transaction::new(TxType::Resolve, 1, 2, None);
+        assert!(acc_man.process_tx(&resolve).is_ok());
+        assert_eq!(balances(&acc_man), (d(6), d(0), d(6), false));
+
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        let chargeback = Transaction::new(TxType::Chargeback, 1, 2, None);
+        assert!(acc_man.process_tx(&chargeback).is_ok());
+        assert_eq!(balances(&acc_man), (d(10), d(0), d(10), true));
+        assert_eq!(acc_man.chargebacks()[0].amount, Decimal::new(4, 0));
+    }
+
+    #[test]
+    fn partial_withdrawal_dispute_and_default_refusal() {
+        let mut acc_man = AccountManager::with_config(Config {
+            withdrawal_disputes: true,
+            ..Config::default()
+        });
+        assert!(acc_man.deposit(1, 1, Decimal::new(10, 0)).is_ok());
+        assert!(acc_man.withdraw(1, 2, Decimal::new(4, 0)).is_ok());
+        let dispute = Transaction::new(TxType::Dispute, 1, 2, Some(Decimal::new(1, 0)));
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        assert_eq!(acc_man.disputable_amount(2), Some(Decimal::new(0, 0)));
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.held, Decimal::new(1, 0));
+        assert_eq!(account.total, Decimal::new(7, 0));
+
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(10, 0)).is_ok());
+        assert!(acc_man.withdraw(1, 2, Decimal::new(4, 0)).is_ok());
+        assert!(!acc_man.is_disputable(2));
+        assert_eq!(
+            acc_man.process_tx(&Transaction::new(TxType::Dispute, 1, 2, None)),
+            Err(ProcessError::NotDisputable)
+        );
+    }
 
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_some());
-        let account: &ClientAccount = maybe_account.unwrap();
-        assert_eq!(account.available, Decimal::new(2, 0));
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.held, Decimal::new(0, 0));
-        assert_eq!(account.locked, false);
-        assert_eq!(account.total, Decimal::new(2, 0));
+    #[test]
+    fn accounts_and_diagnostics_go_to_separate_writers() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 9, 9, 1.0\n\
+             deposit, 9, 9, 1.0\n",
+        )
+        .unwrap();
+        assert_eq!(acc_man.rejections().len(), 1);
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let csv = "type, client, tx, amount\n\
+                   deposit, 1, 1, 2.0\n\
+                   withdraw, 1, 2, 5.0\n";
+        let summary = acc_man
+            .process_csv_to(csv.as_bytes(), &mut out, &mut err)
+            .unwrap();
+        assert_eq!(summary.rejected, 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client,available,held,total,locked\n1,2,0,2,false\n9,1,0,1,false\n"
+        );
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "Error: Row 3: Insufficient Funds\n"
+        );
     }
 
     #[test]
-    fn withdraw_new_account() {
+    fn contains_client_and_transaction() {
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx = Transaction {
-            tx_type: Some(TxType::Withdraw),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(1, 0)),
-            is_disputed: false,
-        };
-        let result = acc_man.process_tx(&tx);
-        assert!(result.is_err());
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_none());
+        assert!(!acc_man.contains_client(1));
+        assert!(acc_man.deposit(1, 7, Decimal::new(1, 0)).is_ok());
+        assert!(acc_man.contains_client(1));
+        assert!(!acc_man.contains_client(7));
+        assert!(acc_man.contains_transaction(7));
+        assert!(!acc_man.contains_transaction(1));
     }
 
     #[test]
-    fn withdraw_duplicate_tx() {
+    fn held_sums_simultaneous_disputes() {
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(9, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx).is_ok());
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Withdraw),
-            client: client_id,
-            tx: 2u32,
-            amount: Some(Decimal::new(1, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx2 = Transaction {
-            tx_type: Some(TxType::Withdraw),
-            client: client_id,
-            tx: 2u32,
-            amount: Some(Decimal::new(1, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx2).is_err());
+        assert!(acc_man.deposit(1, 1, Decimal::new(3, 0)).is_ok());
+        assert!(acc_man.deposit(1, 2, Decimal::new(5, 0)).is_ok());
+        for tx in [1, 2] {
+            assert!(acc_man
+                .process_tx(&Transaction::new(TxType::Dispute, 1, tx, None))
+                .is_ok());
+        }
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(0, 0));
+        assert_eq!(account.held, Decimal::new(8, 0));
+        assert_eq!(account.total, Decimal::new(8, 0));
 
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_some());
-        let account: &ClientAccount = maybe_account.unwrap();
-        assert_eq!(account.available, Decimal::new(8, 0));
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.held, Decimal::new(0, 0));
-        assert_eq!(account.locked, false);
+        let resolve = Transaction::new(TxType::Resolve, 1, 1, None);
+        assert!(acc_man.process_tx(&resolve).is_ok());
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(3, 0));
+        assert_eq!(account.held, Decimal::new(5, 0));
         assert_eq!(account.total, Decimal::new(8, 0));
+        assert_eq!(acc_man.disputed_amount(1), Some(Decimal::new(5, 0)));
     }
 
     #[test]
-    fn withdraw_multiple_tx() {
-        let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(10, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx2 = Transaction {
-            tx_type: Some(TxType::Withdraw),
-            client: client_id,
-            tx: 2u32,
-            amount: Some(Decimal::new(1, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx2).is_ok());
-        let tx3 = Transaction {
-            tx_type: Some(TxType::Withdraw),
-            client: client_id,
-            tx: 3u32,
-            amount: Some(Decimal::new(1, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx3).is_ok());
+    fn locked_account_attempts_are_recorded() {
+        let mut acc_man = AccountManager::with_config(Config {
+            monitor_locked_accounts: true,
+            ..Config::default()
+        });
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        for tx_type in [TxType::Dispute, TxType::Chargeback] {
+            assert!(acc_man
+                .process_tx(&Transaction::new(tx_type, 1, 1, None))
+                .is_ok());
+        }
+        assert!(acc_man.locked_violation_attempts().is_empty());
+        assert_eq!(
+            acc_man.deposit(1, 2, Decimal::new(3, 0)),
+            Err(ProcessError::AccountLocked)
+        );
+        assert!(acc_man.deposit(2, 3, Decimal::new(3, 0)).is_ok());
 
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_some());
-        let account: &ClientAccount = maybe_account.unwrap();
-        assert_eq!(account.available, Decimal::new(8, 0));
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.held, Decimal::new(0, 0));
-        assert_eq!(account.locked, false);
-        assert_eq!(account.total, Decimal::new(8, 0));
+        let attempts: Vec<u32> = acc_man
+            .locked_violation_attempts()
+            .iter()
+            .map(|t| t.tx())
+            .collect();
+        assert_eq!(attempts, vec![2]);
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(0, 0));
+        assert!(!acc_man.contains_transaction(2));
     }
 
     #[test]
-    fn withdraw_insufficient_funds_tx() {
+    fn output_precision_follows_account_currency() {
+        let mut precision = HashMap::new();
+        precision.insert("JPY".to_string(), 0);
+        precision.insert("USD".to_string(), 2);
+        let mut acc_man = AccountManager::with_config(Config {
+            currency_precision: precision,
+            ..Config::default()
+        });
+        assert!(acc_man.deposit(1, 1, Decimal::new(15006, 1)).is_ok());
+        assert!(acc_man.deposit(2, 2, Decimal::new(125, 1)).is_ok());
+        assert!(acc_man.deposit(3, 3, Decimal::new(125, 1)).is_ok());
+        assert!(acc_man.set_currency(1, "JPY").is_ok());
+        assert!(acc_man.set_currency(2, "USD").is_ok());
+        assert_eq!(
+            acc_man.set_currency(4, "USD"),
+            Err(ProcessError::NoSuchAccount)
+        );
+
+        let mut out = Vec::new();
+        acc_man.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client,available,held,total,locked\n\
+             1,1501,0,1501,false\n\
+             2,12.50,0.00,12.50,false\n\
+             3,12.5,0,12.5,false\n"
+        );
+    }
+
+    #[test]
+    fn zeroed_account_keeps_record_and_lock() {
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(10, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx2 = Transaction {
-            tx_type: Some(TxType::Withdraw),
-            client: client_id,
-            tx: 2u32,
-            amount: Some(Decimal::new(11, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx2).is_err());
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.deposit(1, 2, Decimal::new(3, 0)).is_ok());
+        for tx_type in [TxType::Dispute, TxType::Chargeback] {
+            assert!(acc_man
+                .process_tx(&Transaction::new(tx_type, 1, 2, None))
+                .is_ok());
+        }
+        assert!(acc_man.zero_account(1).is_ok());
+        assert_eq!(acc_man.zero_account(2), Err(ProcessError::NoSuchAccount));
 
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_some());
-        let account: &ClientAccount = maybe_account.unwrap();
-        assert_eq!(account.available, Decimal::new(10, 0));
-        assert_eq!(account.client, client_id);
+        assert!(acc_man.contains_client(1));
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(0, 0));
         assert_eq!(account.held, Decimal::new(0, 0));
-        assert_eq!(account.locked, false);
-        assert_eq!(account.total, Decimal::new(10, 0));
+        assert_eq!(account.total, Decimal::new(0, 0));
+        assert!(account.locked);
+        assert_eq!(
+            acc_man.corrections(),
+            &[CorrectionRecord {
+                client: 1,
+                available: Decimal::new(5, 0),
+                held: Decimal::new(0, 0),
+                pending: Decimal::new(0, 0),
+                total: Decimal::new(5, 0),
+            }]
+        );
     }
 
     #[test]
-    fn dispute_a_deposit_tx() {
+    fn zeroing_waits_for_open_disputes_and_pending_withdrawals() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 10.0\n\
+             deposit, 1, 2, 2.0\n\
+             pending, 1, 3, 3.0\n\
+             dispute, 1, 2,\n",
+        )
+        .unwrap();
+        assert_eq!(acc_man.zero_account(1), Err(ProcessError::DisputesOpen));
+        let resolve = Transaction::new(TxType::Resolve, 1, 2, None);
+        assert!(acc_man.process_tx(&resolve).is_ok());
+        assert_eq!(acc_man.zero_account(1), Err(ProcessError::PendingOpen));
+        assert!(acc_man.corrections().is_empty());
+
+        let cancel = Transaction::new(TxType::Cancel, 1, 3, None);
+        assert!(acc_man.process_tx(&cancel).is_ok());
+        assert!(acc_man.zero_account(1).is_ok());
+        assert_eq!(acc_man.process_tx(&cancel), Err(ProcessError::NotPending));
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(0, 0));
+        assert_eq!(account.pending, Decimal::new(0, 0));
+        assert_eq!(account.total, Decimal::new(0, 0));
+    }
+
+    #[test]
+    fn streaming_writes_a_row_per_applied_transaction() {
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(5, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx2 = Transaction {
-            tx_type: Some(TxType::Dispute),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx2).is_ok());
+        let mut out = Vec::new();
+        let summary = acc_man
+            .process_streaming(
+                "type, client, tx, amount\n\
+                 deposit, 1, 1, 2.0\n\
+                 deposit, 2, 2, 1.0\n\
+                 withdraw, 1, 3, 9.0\n\
+                 withdraw, 1, 4, 0.5\n"
+                    .as_bytes(),
+                &mut out,
+            )
+            .unwrap();
+        assert_eq!(summary.applied, 3);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client,available,held,total,locked\n\
+             1,2,0,2,false\n\
+             2,1,0,1,false\n\
+             1,1.5,0,1.5,false\n"
+        );
+    }
 
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_some());
-        let account: &ClientAccount = maybe_account.unwrap();
+    #[test]
+    fn deposit_and_withdrawal_share_tx_ids() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert_eq!(
+            acc_man.withdraw(1, 1, Decimal::new(2, 0)),
+            Err(ProcessError::DuplicateTransaction)
+        );
+        let dispute = Transaction::new(TxType::Dispute, 1, 1, None);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
         assert_eq!(account.available, Decimal::new(0, 0));
-        assert_eq!(account.client, client_id);
         assert_eq!(account.held, Decimal::new(5, 0));
-        assert_eq!(account.locked, false);
-        assert_eq!(account.total, Decimal::new(5, 0));
-        match acc_man.transactions.entry(1u32) {
-            Occupied(e) => assert_eq!(e.get().is_disputed, true),
-            Vacant(_e) => assert!(false),
-        };
+        let stored = acc_man
+            .transactions_of_type(TxType::Deposit)
+            .next()
+            .unwrap();
+        assert_eq!(stored.tx(), 1);
+        assert_eq!(acc_man.transactions_of_type(TxType::Withdraw).count(), 0);
     }
 
     #[test]
-    fn dispute_a_withdraw_tx() {
+    fn shared_tx_id_is_disputed_only_by_its_owner() {
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(10, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx).is_ok());
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Withdraw),
-            client: client_id,
-            tx: 2u32,
-            amount: Some(Decimal::new(9, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx2 = Transaction {
-            tx_type: Some(TxType::Dispute),
-            client: client_id,
-            tx: 2u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx2).is_err());
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.deposit(2, 2, Decimal::new(5, 0)).is_ok());
+        assert_eq!(
+            acc_man.withdraw(2, 1, Decimal::new(2, 0)),
+            Err(ProcessError::DuplicateTransaction)
+        );
+        assert_eq!(
+            acc_man.process_tx(&Transaction::new(TxType::Dispute, 2, 1, None)),
+            Err(ProcessError::ClientMismatch)
+        );
+        assert_eq!(acc_man.accounts[&2u16].held, Decimal::new(0, 0));
+        assert!(acc_man
+            .process_tx(&Transaction::new(TxType::Dispute, 1, 1, None))
+            .is_ok());
+        assert_eq!(acc_man.accounts[&1u16].held, Decimal::new(5, 0));
+        assert_eq!(acc_man.accounts[&2u16].available, Decimal::new(5, 0));
+    }
 
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_some());
-        let account: &ClientAccount = maybe_account.unwrap();
-        assert_eq!(account.available, Decimal::new(1, 0));
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.held, Decimal::new(0, 0));
-        assert_eq!(account.locked, false);
-        assert_eq!(account.total, Decimal::new(1, 0));
-        match acc_man.transactions.entry(1u32) {
-            Occupied(e) => assert_eq!(e.get().is_disputed, false),
-            Vacant(_e) => assert!(false),
-        };
+    #[test]
+    fn dispute_crossing_held_threshold_raises_alert() {
+        let mut acc_man = AccountManager::with_config(Config {
+            held_alert_threshold: Some(Decimal::new(10, 0)),
+            ..Config::default()
+        });
+        for tx in 1..=4 {
+            assert!(acc_man.deposit(1, tx, Decimal::new(4, 0)).is_ok());
+        }
+        for tx in 1..=4 {
+            let dispute = Transaction::new(TxType::Dispute, 1, tx, None);
+            assert!(acc_man.process_tx(&dispute).is_ok());
+            if tx < 3 {
+                assert!(acc_man.held_alerts().is_empty());
+            }
+        }
+        assert_eq!(
+            acc_man.held_alerts(),
+            &[HeldAlert {
+                client: 1,
+                tx: 3,
+                held: Decimal::new(12, 0),
+            }]
+        );
     }
 
     #[test]
-    fn resolve_a_dispute_tx() {
+    fn dedup_skips_tx_ids_seen_by_an_earlier_run() {
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(9, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx2 = Transaction {
-            tx_type: Some(TxType::Dispute),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx2).is_ok());
-        let tx3 = Transaction {
-            tx_type: Some(TxType::Resolve),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx3).is_ok());
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_some());
-        let account: &ClientAccount = maybe_account.unwrap();
-        assert_eq!(account.available, Decimal::new(9, 0));
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.held, Decimal::new(0, 0));
-        assert_eq!(account.locked, false);
-        assert_eq!(account.total, Decimal::new(9, 0));
-        match acc_man.transactions.entry(1u32) {
-            Occupied(e) => assert_eq!(e.get().is_disputed, false),
-            Vacant(_e) => assert!(false),
-        };
+        let mut seen = HashSet::new();
+        seen.insert(1u32);
+        let summary = acc_man
+            .process_csv_with_dedup(
+                "type, client, tx, amount\n\
+                 deposit, 1, 1, 5.0\n\
+                 deposit, 1, 2, 3.0\n\
+                 withdraw, 1, 3, 1.0\n"
+                    .as_bytes(),
+                seen,
+            )
+            .unwrap();
+        assert_eq!(summary.rows, 3);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(summary.applied, 2);
+        assert_eq!(summary.rejected, 0);
+        assert!(!acc_man.contains_transaction(1));
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(2, 0));
+    }
+
+    #[test]
+    fn held_ratio_after_dispute() {
+        let mut acc_man = AccountManager::default();
+        assert!(acc_man.deposit(1, 1, Decimal::new(1, 0)).is_ok());
+        assert!(acc_man.deposit(1, 2, Decimal::new(3, 0)).is_ok());
+        assert!(acc_man.deposit(2, 3, Decimal::new(4, 0)).is_ok());
+        assert!(acc_man.withdraw(2, 4, Decimal::new(4, 0)).is_ok());
+        assert_eq!(
+            acc_man.accounts.get(&1u16).unwrap().held_ratio(),
+            Some(Decimal::new(0, 0))
+        );
+        assert_eq!(acc_man.accounts.get(&2u16).unwrap().held_ratio(), None);
+
+        let dispute = Transaction::new(TxType::Dispute, 1, 2, None);
+        assert!(acc_man.process_tx(&dispute).is_ok());
+        assert_eq!(
+            acc_man.accounts.get(&1u16).unwrap().held_ratio(),
+            Some(Decimal::new(75, 2))
+        );
+        assert_eq!(
+            acc_man.accounts_high_held_ratio(Decimal::new(5, 1)),
+            vec![1]
+        );
+        assert!(acc_man
+            .accounts_high_held_ratio(Decimal::new(8, 1))
+            .is_empty());
+    }
+
+    #[test]
+    fn transactions_csv_lists_the_ledger() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 5.0\n\
+             withdraw, 2, 2, 1.0\n\
+             deposit, 2, 3, 2.5\n\
+             withdraw, 1, 4, 1.0\n\
+             dispute, 1, 1,\n",
+        )
+        .unwrap();
+        assert!(acc_man.deposit(1, 5, Decimal::new(1, 0)).is_ok());
+        let mut out = Vec::new();
+        acc_man.write_transactions_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "type,client,tx,amount,is_disputed\n\
+             deposit,1,1,5,true\n\
+             deposit,2,3,2.5,false\n\
+             withdraw,1,4,1,false\n\
+             deposit,1,5,1,false\n"
+        );
+    }
+
+    #[test]
+    fn non_numeric_client_is_rejected_cleanly() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, abc, 1, 1.0\n\
+             deposit, , 2, 1.0\n\
+             deposit, -3, 3, 1.0\n\
+             deposit, 4, 4, 1.0\n",
+        )
+        .unwrap();
+        let rejected: Vec<(u64, ProcessError)> = acc_man
+            .rejections()
+            .iter()
+            .map(|r| (r.row, r.reason.clone()))
+            .collect();
+        assert_eq!(
+            rejected,
+            vec![
+                (2, ProcessError::InvalidClientId("abc".to_string())),
+                (3, ProcessError::InvalidClientId("".to_string())),
+                (4, ProcessError::InvalidClientId("-3".to_string())),
+            ]
+        );
+        assert_eq!(
+            acc_man.rejections()[0].to_string(),
+            "Row 2: Invalid Client Id: 'abc'"
+        );
+        assert!(acc_man.contains_client(4));
     }
 
     #[test]
-    fn resolve_a_non_dispute_tx() {
+    fn amounts_in_either_decimal_format() {
+        let dot = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit,1,1,\"1,000.50\"\n\
+             deposit, 2, 2, 2.25\n",
+        )
+        .unwrap();
+        let mut comma = AccountManager::with_config(Config {
+            decimal_format: DecimalFormat::Comma,
+            ..Config::default()
+        });
+        comma
+            .process_csv(
+                "type, client, tx, amount\n\
+                 deposit,1,1,\"1.000,50\"\n\
+                 deposit,2,2,\"2,25\"\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        for acc_man in [&dot, &comma] {
+            assert!(acc_man.rejections().is_empty());
+            let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+            assert_eq!(account.available, Decimal::new(100050, 2));
+            let account: &ClientAccount = acc_man.accounts.get(&2u16).unwrap();
+            assert_eq!(account.available, Decimal::new(225, 2));
+        }
+    }
+
+    #[test]
+    fn misgrouped_amounts_are_rejected() {
+        let mut dot = AccountManager::default();
+        let summary = dot
+            .process_csv(
+                "type, client, tx, amount\n\
+                 deposit,1,1,\"1,5\"\n\
+                 deposit,1,2,\"1,00,000\"\n\
+                 deposit,1,3,\"1.000,5\"\n\
+                 deposit,1,4,\"12,345.6\"\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        assert_eq!(summary.rejected, 3);
+        assert_eq!(
+            dot.rejections()[0].reason,
+            ProcessError::MalformedRow("invalid amount: 1,5".to_string())
+        );
+        assert_eq!(dot.accounts[&1u16].available, Decimal::new(123456, 1));
+
+        let mut comma = AccountManager::with_config(Config {
+            decimal_format: DecimalFormat::Comma,
+            ..Config::default()
+        });
+        let summary = comma
+            .process_csv(
+                "type, client, tx, amount\n\
+                 deposit,1,1,\"1.5,0\"\n\
+                 deposit,1,2,\"1,5.0\"\n\
+                 deposit,1,3,\"1.234,5\"\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        assert_eq!(summary.rejected, 2);
+        assert_eq!(comma.accounts[&1u16].available, Decimal::new(12345, 1));
+    }
+
+    #[test]
+    fn amounts_in_minor_units() {
+        let mut acc_man = AccountManager::with_config(Config {
+            decimal_format: DecimalFormat::MinorUnits,
+            ..Config::default()
+        });
+        let summary = acc_man
+            .process_csv(
+                "type, client, tx, amount, scale\n\
+                 deposit, 1, 1, 150, 2\n\
+                 deposit, 2, 2, 7, 0\n\
+                 dispute, 2, 2,\n\
+                 deposit, 3, 3, 1.5, 1\n\
+                 deposit, 3, 4, 150,\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        assert_eq!(summary.applied, 3);
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(150, 2));
+        let account: &ClientAccount = acc_man.accounts.get(&2u16).unwrap();
+        assert_eq!(account.held, Decimal::new(7, 0));
+        let reasons: Vec<&ProcessError> = acc_man.rejections().iter().map(|r| &r.reason).collect();
+        assert_eq!(
+            reasons,
+            [
+                &ProcessError::MalformedRow(
+                    "invalid minor units: invalid digit found in string".to_string()
+                ),
+                &ProcessError::MalformedRow(
+                    "invalid scale: cannot parse integer from empty string".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn throttled_csv_is_paced() {
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(9, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx3 = Transaction {
-            tx_type: Some(TxType::Resolve),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx3).is_err());
+        let started = Instant::now();
+        let summary = acc_man
+            .process_csv_throttled(
+                "type, client, tx, amount\n\
+                 deposit, 1, 1, 1.0\n\
+                 deposit, 1, 2, 1.0\n\
+                 deposit, 1, 3, 1.0\n\
+                 deposit, 1, 4, 1.0\n\
+                 deposit, 1, 5, 1.0\n"
+                    .as_bytes(),
+                100,
+            )
+            .unwrap();
+        assert_eq!(summary.applied, 5);
+        assert!(started.elapsed() >= Duration::from_millis(40));
+        assert_eq!(acc_man.accounts[&1u16].total, Decimal::new(5, 0));
     }
 
     #[test]
-    fn chargeback_a_dispute_tx() {
+    fn filter_rejects_large_deposits() {
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(9, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx2 = Transaction {
-            tx_type: Some(TxType::Dispute),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx2).is_ok());
-        let tx3 = Transaction {
-            tx_type: Some(TxType::Chargeback),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx3).is_ok());
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_some());
-        let account: &ClientAccount = maybe_account.unwrap();
-        assert_eq!(account.available, Decimal::new(0, 0));
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.held, Decimal::new(0, 0));
-        assert_eq!(account.locked, true);
-        assert_eq!(account.total, Decimal::new(0, 0));
-        match acc_man.transactions.entry(1u32) {
-            Occupied(e) => assert_eq!(e.get().is_disputed, true),
-            Vacant(_e) => assert!(false),
+        acc_man.set_tx_filter(Box::new(|tx: &Transaction| {
+            tx.amount().is_none_or(|a| a <= Decimal::new(100, 0))
+        }));
+        let summary = acc_man
+            .process_csv(
+                "type, client, tx, amount\n\
+                 deposit, 1, 1, 100.0\n\
+                 deposit, 1, 2, 100.01\n\
+                 dispute, 1, 1,\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        assert_eq!(summary.applied, 2);
+        assert_eq!(acc_man.rejections()[0].tx, Some(2));
+        assert_eq!(acc_man.rejections()[0].reason, ProcessError::FilteredOut);
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.held, Decimal::new(100, 0));
+    }
+
+    #[test]
+    fn total_tracks_its_parts_through_every_transaction_type() {
+        let mut acc_man = AccountManager::with_config(Config {
+            withdrawal_disputes: true,
+            ..Config::default()
+        });
+        let rows = [
+            (TxType::Deposit, 1, Some(20)),
+            (TxType::Withdraw, 2, Some(3)),
+            (TxType::Pending, 3, Some(4)),
+            (TxType::Settle, 3, None),
+            (TxType::Pending, 4, Some(2)),
+            (TxType::Cancel, 4, None),
+            (TxType::Dispute, 2, None),
+            (TxType::Resolve, 2, None),
+            (TxType::Dispute, 1, None),
+            (TxType::Chargeback, 1, None),
+        ];
+        let mut totals = vec![];
+        for (tx_type, tx, amount) in rows.iter().cloned() {
+            let amount = amount.map(|a| Decimal::new(a, 0));
+            assert!(acc_man
+                .process_tx(&Transaction::new(tx_type, 1, tx, amount))
+                .is_ok());
+            let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+            assert_eq!(
+                account.total,
+                total_checked(account.available, account.held, account.pending).unwrap()
+            );
+            totals.push(account.total);
+        }
+        let expected: Vec<Decimal> = [20, 17, 17, 13, 13, 13, 16, 13, 13, -7]
+            .iter()
+            .map(|t| Decimal::new(*t, 0))
+            .collect();
+        assert_eq!(totals, expected);
+    }
+
+    #[test]
+    fn dispute_outside_the_window_is_rejected() {
+        let mut acc_man = AccountManager::with_config(Config {
+            dispute_window: Some(chrono::Duration::days(10)),
+            ..Config::default()
+        });
+        let day = |d: u32| Utc.with_ymd_and_hms(2024, 1, d, 0, 0, 0).unwrap();
+        let deposit = |tx: u32| {
+            Transaction::new(TxType::Deposit, 1, tx, Some(Decimal::new(5, 0)))
+                .with_timestamp(day(1))
         };
+        let dispute =
+            |tx: u32, d: u32| Transaction::new(TxType::Dispute, 1, tx, None).with_timestamp(day(d));
+        assert!(acc_man.process_tx(&deposit(1)).is_ok());
+        assert!(acc_man.process_tx(&deposit(2)).is_ok());
+        assert_eq!(
+            acc_man.process_tx(&dispute(1, 12)),
+            Err(ProcessError::DisputeWindowExpired)
+        );
+        assert!(acc_man.process_tx(&dispute(2, 11)).is_ok());
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.held, Decimal::new(5, 0));
+        assert_eq!(account.available, Decimal::new(5, 0));
     }
 
     #[test]
-    fn cant_deposit_to_a_locked_account() {
+    fn account_snapshot_is_independent_of_later_changes() {
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(9, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx2 = Transaction {
-            tx_type: Some(TxType::Dispute),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx2).is_ok());
-        let tx3 = Transaction {
-            tx_type: Some(TxType::Chargeback),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx3).is_ok());
-        let tx4 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(9, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx4).is_err());
-        let maybe_account = acc_man.accounts.get(&client_id);
-        assert!(maybe_account.is_some());
-        let account: &ClientAccount = maybe_account.unwrap();
-        assert_eq!(account.available, Decimal::new(0, 0));
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.held, Decimal::new(0, 0));
-        assert_eq!(account.locked, true);
-        assert_eq!(account.total, Decimal::new(0, 0));
-        match acc_man.transactions.entry(1u32) {
-            Occupied(e) => assert_eq!(e.get().is_disputed, true),
-            Vacant(_e) => assert!(false),
-        };
+        assert!(acc_man.deposit(2, 1, Decimal::new(5, 0)).is_ok());
+        assert!(acc_man.deposit(1, 2, Decimal::new(3, 0)).is_ok());
+        let snapshot = acc_man.snapshot_accounts();
+        assert_eq!(
+            snapshot.iter().collect::<Vec<_>>(),
+            vec![
+                acc_man.accounts.get(&1u16).unwrap(),
+                acc_man.accounts.get(&2u16).unwrap()
+            ]
+        );
+
+        assert!(acc_man.withdraw(2, 3, Decimal::new(4, 0)).is_ok());
+        let reporter = std::thread::spawn(move || snapshot[1].available());
+        assert_eq!(reporter.join().unwrap(), Decimal::new(5, 0));
+        assert_eq!(
+            acc_man.accounts.get(&2u16).unwrap().available,
+            Decimal::new(1, 0)
+        );
     }
 
     #[test]
-    fn chargeback_a_non_dispute_tx() {
+    fn strict_precision_rejects_rather_than_rounds() {
+        let mut acc_man = AccountManager::with_config(Config {
+            strict_precision: true,
+            ..Config::default()
+        });
+        let summary = acc_man
+            .process_csv(
+                "type, client, tx, amount\n\
+                 deposit, 1, 1, 1.23456\n\
+                 deposit, 1, 2, 1.2345\n\
+                 deposit, 1, 3, 1.50000\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        assert_eq!(summary.applied, 2);
+        assert_eq!(acc_man.rejections()[0].tx, Some(1));
+        assert_eq!(
+            acc_man.rejections()[0].reason,
+            ProcessError::TooPrecise(DECIMAL_PRECISION)
+        );
+        assert_eq!(
+            acc_man.rejections()[0].reason.to_string(),
+            "Amount has more than 4 Decimal Places"
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(27345, 4));
+    }
+
+    #[test]
+    fn deltas_reported_for_each_transaction_type() {
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(9, 0)),
-            is_disputed: false,
+        let mut delta = |tx_type: TxType, tx: u32, amount: Option<i64>| {
+            let amount = amount.map(|a| Decimal::new(a, 0));
+            let d = acc_man
+                .process_tx_delta(&Transaction::new(tx_type, 1, tx, amount))
+                .unwrap();
+            (d.available, d.held)
         };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx3 = Transaction {
-            tx_type: Some(TxType::Chargeback),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
+        let d = |available: i64, held: i64| (Decimal::new(available, 0), Decimal::new(held, 0));
+        assert_eq!(delta(TxType::Deposit, 1, Some(10)), d(10, 0));
+        assert_eq!(delta(TxType::Withdraw, 2, Some(3)), d(-3, 0));
+        assert_eq!(delta(TxType::Deposit, 3, Some(4)), d(4, 0));
+        assert_eq!(delta(TxType::Dispute, 1, None), d(-10, 10));
+        assert_eq!(delta(TxType::Resolve, 1, None), d(10, -10));
+        assert_eq!(delta(TxType::Dispute, 3, None), d(-4, 4));
+        assert_eq!(delta(TxType::Chargeback, 3, None), d(0, -4));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_file_is_processed() {
+        use parquet::column::writer::ColumnWriter;
+        use parquet::data_type::ByteArray;
+        use parquet::file::properties::WriterProperties;
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+        use std::sync::Arc;
+
+        let path = std::env::temp_dir().join(format!("txns_{}.parquet", std::process::id()));
+        let schema = parse_message_type(
+            "message transactions {
+                REQUIRED BYTE_ARRAY type (UTF8);
+                REQUIRED INT32 client;
+                REQUIRED INT64 tx;
+                OPTIONAL BYTE_ARRAY amount (UTF8);
+            }",
+        )
+        .unwrap();
+        let file = std::fs::File::create(&path).unwrap();
+        let props = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(file, Arc::new(schema), props).unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+        let text = |values: &[&str]| -> Vec<ByteArray> {
+            values.iter().map(|v| ByteArray::from(*v)).collect()
         };
-        assert!(acc_man.process_tx(&tx3).is_err());
+        while let Some(mut column) = row_group.next_column().unwrap() {
+            match column.untyped() {
+                ColumnWriter::ByteArrayColumnWriter(w) if w.get_descriptor().name() == "type" => {
+                    let types = text(&["deposit", "withdraw", "dispute", "deposit"]);
+                    w.write_batch(&types, None, None).unwrap();
+                }
+                ColumnWriter::ByteArrayColumnWriter(w) => {
+                    // The dispute row's amount is null.
+                    let amounts = text(&["10.5", "0.5", "2"]);
+                    w.write_batch(&amounts, Some(&[1, 1, 0, 1]), None).unwrap();
+                }
+                ColumnWriter::Int32ColumnWriter(w) => {
+                    w.write_batch(&[1, 1, 1, 2], None, None).unwrap();
+                }
+                ColumnWriter::Int64ColumnWriter(w) => {
+                    w.write_batch(&[1, 2, 1, 3], None, None).unwrap();
+                }
+                _ => unreachable!(),
+            }
+            column.close().unwrap();
+        }
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        let mut acc_man = AccountManager::default();
+        let summary = acc_man.process_parquet(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(summary.rows, 4);
+        assert_eq!(summary.applied, 4);
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(-5, 1));
+        assert_eq!(account.held, Decimal::new(105, 1));
+        let account: &ClientAccount = acc_man.accounts.get(&2u16).unwrap();
+        assert_eq!(account.available, Decimal::new(2, 0));
     }
 
+    #[cfg(feature = "parquet")]
     #[test]
-    fn chargeback_a_non_existent_tx() {
+    fn parquet_decimal_amounts_are_processed() {
+        use parquet::column::writer::ColumnWriter;
+        use parquet::data_type::ByteArray;
+        use parquet::file::properties::WriterProperties;
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+        use std::sync::Arc;
+
+        let path = std::env::temp_dir().join(format!("decimals_{}.parquet", std::process::id()));
+        let schema = parse_message_type(
+            "message transactions {
+                REQUIRED BYTE_ARRAY type (UTF8);
+                REQUIRED INT32 client;
+                REQUIRED INT32 tx;
+                OPTIONAL INT64 amount (DECIMAL(18,4));
+            }",
+        )
+        .unwrap();
+        let file = std::fs::File::create(&path).unwrap();
+        let props = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(file, Arc::new(schema), props).unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+        while let Some(mut column) = row_group.next_column().unwrap() {
+            match column.untyped() {
+                ColumnWriter::ByteArrayColumnWriter(w) => {
+                    let types: Vec<ByteArray> = ["deposit", "withdraw", "dispute"]
+                        .iter()
+                        .map(|v| ByteArray::from(*v))
+                        .collect();
+                    w.write_batch(&types, None, None).unwrap();
+                }
+                ColumnWriter::Int32ColumnWriter(w) if w.get_descriptor().name() == "client" => {
+                    w.write_batch(&[1, 1, 1], None, None).unwrap();
+                }
+                ColumnWriter::Int32ColumnWriter(w) => {
+                    w.write_batch(&[1, 2, 1], None, None).unwrap();
+                }
+                ColumnWriter::Int64ColumnWriter(w) => {
+                    // 10.5 & 0.25 at scale 4; the dispute row's amount is null.
+                    w.write_batch(&[105000, 2500], Some(&[1, 1, 0]), None)
+                        .unwrap();
+                }
+                _ => unreachable!(),
+            }
+            column.close().unwrap();
+        }
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx1 = Transaction {
-            tx_type: Some(TxType::Deposit),
-            client: client_id,
-            tx: 1u32,
-            amount: Some(Decimal::new(9, 0)),
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx1).is_ok());
-        let tx3 = Transaction {
-            tx_type: Some(TxType::Chargeback),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx3).is_err());
+        let summary = acc_man.process_parquet(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(summary.applied, 3);
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(-25, 2));
+        assert_eq!(account.held, Decimal::new(105, 1));
     }
 
     #[test]
-    fn chargeback_a_non_existent_customer() {
+    fn charged_back_totals() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 7.5\n\
+             deposit, 1, 2, 2.0\n\
+             deposit, 2, 3, 4.0\n\
+             dispute, 1, 1,\n\
+             chargeback, 1, 1,\n\
+             dispute, 2, 3,\n",
+        )
+        .unwrap();
+        assert_eq!(acc_man.total_charged_back(), Ok(Decimal::new(75, 1)));
+        let mut expected = HashMap::new();
+        expected.insert(1u16, Decimal::new(75, 1));
+        assert_eq!(acc_man.charged_back_by_client(), Ok(expected));
+
+        assert!(acc_man
+            .process_tx(&Transaction::new(TxType::Chargeback, 2, 3, None))
+            .is_ok());
+        assert_eq!(acc_man.total_charged_back(), Ok(Decimal::new(115, 1)));
+        assert_eq!(
+            acc_man.charged_back_by_client().unwrap()[&2],
+            Decimal::new(4, 0)
+        );
+
+        for tx in 6..8 {
+            acc_man.chargebacks.push(ChargebackRecord {
+                client: 2,
+                tx,
+                amount: Decimal::MAX,
+                locked: true,
+            });
+        }
+        assert_eq!(acc_man.total_charged_back(), Err(ProcessError::Overflow));
+        assert_eq!(
+            acc_man.charged_back_by_client(),
+            Err(ProcessError::Overflow)
+        );
+        assert_eq!(acc_man.stats(), Err(ProcessError::Overflow));
+    }
+
+    #[test]
+    fn invalid_utf8_row_is_reported() {
+        let mut input = b"type, client, tx, amount\ndeposit, 1, 1, 5.0\n".to_vec();
+        input.extend_from_slice(b"deposit, 1, 2, 1.0\xff\n");
+        input.extend_from_slice(b"deposit, 1, 3, 2.0,,false,note \xfe\n");
+        input.extend_from_slice(b"deposit, 1, 4, 3.0\n");
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx3 = Transaction {
-            tx_type: Some(TxType::Chargeback),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx3).is_err());
+        let summary = acc_man.process_csv(&input[..]).unwrap();
+        assert_eq!(summary.rows, 4);
+        assert_eq!(summary.applied, 3);
+        assert_eq!(acc_man.rejections()[0].row, 3);
+        assert_eq!(
+            acc_man.rejections()[0].reason,
+            ProcessError::MalformedRow("invalid UTF-8 in field 3".to_string())
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(10, 0));
     }
 
     #[test]
-    fn resolve_a_non_existent_tx() {
+    fn stats_after_mixed_transactions() {
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 10.0\n\
+             deposit, 2, 2, 4.0\n\
+             deposit, 3, 3, 2.5\n\
+             withdraw, 1, 4, 3.0\n\
+             dispute, 2, 2,\n\
+             chargeback, 2, 2,\n\
+             dispute, 3, 3,\n",
+        )
+        .unwrap();
+        assert_eq!(
+            acc_man.stats(),
+            Ok(Stats {
+                accounts: 3,
+                locked: 1,
+                total_available: Decimal::new(7, 0),
+                total_held: Decimal::new(25, 1),
+                // The charged back transaction is no longer an open dispute.
+                disputed_count: 1,
+                charged_back_total: Decimal::new(4, 0),
+            })
+        );
+    }
+
+    #[test]
+    fn custom_delimiters_for_input_and_output() {
+        let mut acc_man = AccountManager::with_config(Config {
+            delimiter: b';',
+            ..Config::default()
+        });
+        let summary = acc_man
+            .process_csv(
+                "type; client; tx; amount\n\
+                 deposit; 1; 1; 2.5\n\
+                 deposit; 2; 2; 1.0\n\
+                 dispute; 2; 2;\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        assert_eq!(summary.applied, 3);
+        assert_eq!(
+            acc_man.accounts.get(&2u16).unwrap().held,
+            Decimal::new(1, 0)
+        );
+
+        acc_man.config.delimiter = b'\t';
+        let mut out = vec![];
+        acc_man.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client\tavailable\theld\ttotal\tlocked\n\
+             1\t2.5\t0\t2.5\tfalse\n\
+             2\t0\t1\t1\tfalse\n"
+        );
+    }
+
+    #[test]
+    fn dispute_follows_a_reassigned_deposit() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 10.0\n\
+             deposit, 1, 2, 3.0\n\
+             deposit, 2, 3, 1.0\n",
+        )
+        .unwrap();
+        assert!(acc_man.reassign_transaction(1, 2).is_ok());
+        let before = acc_man.snapshot_accounts();
+        for tx_type in [TxType::Dispute, TxType::Resolve, TxType::Chargeback] {
+            assert_eq!(
+                acc_man.process_tx(&Transaction::new(tx_type, 1, 1, None)),
+                Err(ProcessError::ClientMismatch)
+            );
+        }
+        assert_eq!(acc_man.snapshot_accounts(), before);
+        assert!(acc_man
+            .process_tx(&Transaction::new(TxType::Dispute, 2, 1, None))
+            .is_ok());
+
+        let old: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(old.available, Decimal::new(3, 0));
+        assert_eq!(old.held, Decimal::new(0, 0));
+        assert_eq!(old.total, Decimal::new(3, 0));
+        let new: &ClientAccount = acc_man.accounts.get(&2u16).unwrap();
+        assert_eq!(new.available, Decimal::new(1, 0));
+        assert_eq!(new.held, Decimal::new(10, 0));
+        assert_eq!(new.total, Decimal::new(11, 0));
+        assert_eq!(
+            acc_man.reassign_transaction(1, 1),
+            Err(ProcessError::AlreadyDisputed)
+        );
+    }
+
+    #[test]
+    fn disputes_csv_lists_open_disputes() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 10.0\n\
+             deposit, 1, 2, 3.0\n\
+             deposit, 2, 3, 4.5\n\
+             dispute, 1, 2,\n\
+             resolve, 1, 2,\n\
+             dispute, 2, 3,\n",
+        )
+        .unwrap();
+        let at = Utc.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap();
+        assert!(acc_man
+            .process_tx(
+                &Transaction::new(TxType::Dispute, 1, 1, Some(Decimal::new(4, 0)))
+                    .with_timestamp(at)
+            )
+            .is_ok());
+        let mut out = vec![];
+        acc_man.write_disputes_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client,tx,amount,disputed_at\n\
+             1,1,4,2024-05-01T09:30:00+00:00\n\
+             2,3,4.5,\n"
+        );
+    }
+
+    #[test]
+    fn amounts_over_the_limit_are_rejected() {
+        let mut acc_man = AccountManager::with_config(Config {
+            max_tx_amount: Some(Decimal::new(1000, 0)),
+            ..Config::default()
+        });
+        assert_eq!(
+            acc_man.deposit(1, 1, Decimal::new(100001, 2)),
+            Err(ProcessError::AmountTooLarge)
+        );
+        assert!(acc_man.deposit(1, 2, Decimal::new(1000, 0)).is_ok());
+        assert!(acc_man.deposit(1, 3, Decimal::new(500, 0)).is_ok());
+        assert_eq!(
+            acc_man.withdraw(1, 4, Decimal::new(1500, 0)),
+            Err(ProcessError::AmountTooLarge)
+        );
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.available, Decimal::new(1500, 0));
+    }
+
+    #[test]
+    fn processing_resumes_from_an_offset() {
+        let header = "type, client, tx, amount\n";
+        let first_half = "deposit, 1, 1, 1.0\ndeposit, 1, 2, 2.0\n";
+        let second_half = "deposit, 1, 3, 4.0\ndeposit, 2, 4, 8.0\n";
+        let path = std::env::temp_dir().join(format!("resume_{}.csv", std::process::id()));
+        std::fs::write(&path, format!("{}{}{}", header, first_half, second_half)).unwrap();
+
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx3 = Transaction {
-            tx_type: Some(TxType::Resolve),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
+        let offset = (header.len() + first_half.len()) as u64;
+        let summary = acc_man.process_file_from_offset(&path, offset).unwrap();
+        assert_eq!(summary.applied, 2);
+        assert!(!acc_man.contains_transaction(1));
+        assert!(!acc_man.contains_transaction(2));
+        assert_eq!(
+            acc_man.accounts.get(&1u16).unwrap().available,
+            Decimal::new(4, 0)
+        );
+        assert_eq!(
+            acc_man.accounts.get(&2u16).unwrap().available,
+            Decimal::new(8, 0)
+        );
+
+        let mut acc_man = AccountManager::default();
+        let summary = acc_man.process_file_from_offset(&path, 0).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(summary.applied, 4);
+    }
+
+    #[test]
+    fn dispute_and_resolve_apply_in_order_within_a_batch() {
+        let batch = || {
+            vec![
+                Transaction::new(TxType::Deposit, 2, 3, Some(Decimal::new(1, 0))),
+                Transaction::new(TxType::Deposit, 1, 1, Some(Decimal::new(5, 0))),
+                Transaction::new(TxType::Dispute, 1, 1, None),
+                Transaction::new(TxType::Resolve, 1, 1, None),
+                Transaction::new(TxType::Deposit, 1, 2, Some(Decimal::new(2, 0))),
+            ]
         };
-        assert!(acc_man.process_tx(&tx3).is_err());
+        let mut acc_man = AccountManager::default();
+        acc_man.process_batch(batch(), |tx, e| panic!("tx {} rejected: {}", tx.tx(), e));
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.available, Decimal::new(7, 0));
+
+        let acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 5.0\n\
+             dispute, 1, 1,\n\
+             resolve, 1, 1,\n",
+        )
+        .unwrap();
+        assert!(acc_man.rejections().is_empty());
+        let account: &ClientAccount = acc_man.accounts.get(&1u16).unwrap();
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.available, Decimal::new(5, 0));
     }
 
     #[test]
-    fn dispute_a_non_existent_tx() {
+    fn new_account_derives_its_total() {
+        let account =
+            ClientAccount::new(7, Decimal::new(125, 1), Decimal::new(25, 1), true).unwrap();
+        assert_eq!(account.client(), 7);
+        assert_eq!(account.total(), Decimal::new(15, 0));
+        assert_eq!(account.pending(), Decimal::new(0, 0));
+        assert!(account.locked());
+        assert_eq!(
+            ClientAccount::new(7, Decimal::MAX, Decimal::new(1, 0), false),
+            Err(ProcessError::Overflow)
+        );
+    }
+
+    #[test]
+    fn only_empty_accounts_can_be_closed() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 5.0\n\
+             withdraw, 1, 2, 5.0\n\
+             deposit, 2, 3, 1.5\n",
+        )
+        .unwrap();
+        assert_eq!(acc_man.close_account(2), Err(ProcessError::BalanceNotZero));
+        assert!(acc_man.contains_client(2));
+
+        let closed = acc_man.close_account(1).unwrap();
+        assert_eq!(closed.client(), 1);
+        assert_eq!(closed.total(), Decimal::new(0, 0));
+        assert!(!acc_man.contains_client(1));
+        assert_eq!(acc_man.close_account(1), Err(ProcessError::NoSuchAccount));
+        assert_eq!(
+            acc_man.process_tx(&Transaction::new(TxType::Dispute, 1, 1, None)),
+            Err(ProcessError::AccountMissingForTransaction)
+        );
+    }
+
+    #[test]
+    fn locked_or_frozen_account_cannot_be_closed() {
+        let mut acc_man = AccountManager::from_csv_str(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 5.0\n\
+             dispute, 1, 1,\n\
+             chargeback, 1, 1,\n\
+             deposit, 2, 2, 1.0\n\
+             withdraw, 2, 3, 1.0\n\
+             freeze, 2, 4,\n",
+        )
+        .unwrap();
+        assert_eq!(acc_man.accounts[&1u16].total, Decimal::new(0, 0));
+        assert_eq!(acc_man.close_account(1), Err(ProcessError::AccountLocked));
+        assert_eq!(acc_man.close_account(2), Err(ProcessError::AccountFrozen));
+        assert_eq!(
+            acc_man.deposit(1, 5, Decimal::new(3, 0)),
+            Err(ProcessError::AccountLocked)
+        );
+        assert!(acc_man.accounts[&1u16].locked);
+        assert_eq!(acc_man.accounts[&1u16].total, Decimal::new(0, 0));
+    }
+
+    #[test]
+    fn account_with_an_open_dispute_cannot_be_closed() {
         let mut acc_man = AccountManager::default();
-        let client_id = 1u16;
-        let tx3 = Transaction {
-            tx_type: Some(TxType::Dispute),
-            client: client_id,
-            tx: 1u32,
-            amount: None,
-            is_disputed: false,
-        };
-        assert!(acc_man.process_tx(&tx3).is_err());
+        assert!(acc_man.deposit(1, 1, Decimal::new(5, 0)).is_ok());
+        assert_eq!(
+            acc_man.process_tx(&Transaction::new(
+                TxType::Dispute,
+                1,
+                1,
+                Some(Decimal::new(0, 0))
+            )),
+            Err(ProcessError::ZeroAmount)
+        );
+        assert!(!acc_man.transactions[&1u32].is_disputed);
+
+        assert!(acc_man
+            .process_tx(&Transaction::new(
+                TxType::Dispute,
+                1,
+                1,
+                Some(Decimal::new(2, 0))
+            ))
+            .is_ok());
+        assert_eq!(acc_man.close_account(1), Err(ProcessError::DisputesOpen));
+        assert_eq!(acc_man.accounts[&1u16].held, Decimal::new(2, 0));
     }
 }
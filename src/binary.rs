@@ -0,0 +1,137 @@
+//! A compact bincode encoding of an `AccountManager`'s accounts & transactions, for fast
+//! checkpoints. Decimals are kept in their 16 byte form rather than as text.
+
+use crate::money::Money;
+use crate::ClientAccount;
+use crate::Transaction;
+use crate::TxType;
+use chrono::DateTime;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::error::Error;
+
+#[derive(Serialize, Deserialize)]
+struct State {
+    accounts: Vec<AccountState>,
+    transactions: Vec<TransactionState>,
+    tx_order: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AccountState {
+    client: u16,
+    available: [u8; 16],
+    held: [u8; 16],
+    pending: [u8; 16],
+    total: [u8; 16],
+    locked: bool,
+    frozen: bool,
+    label: Option<String>,
+    currency: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TransactionState {
+    tx_type: Option<String>,
+    client: u16,
+    tx: u32,
+    amount: Option<[u8; 16]>,
+    /// Seconds & nanoseconds since the epoch.
+    timestamp: Option<(i64, u32)>,
+    is_disputed: bool,
+    disputed_amount: Option<[u8; 16]>,
+    charged_back: bool,
+}
+
+pub(crate) type Parts = (
+    HashMap<u16, ClientAccount>,
+    HashMap<u32, Transaction>,
+    VecDeque<u32>,
+);
+
+pub(crate) fn encode(
+    accounts: &HashMap<u16, ClientAccount>,
+    transactions: &HashMap<u32, Transaction>,
+    tx_order: &VecDeque<u32>,
+) -> Vec<u8> {
+    let state = State {
+        accounts: accounts
+            .values()
+            .map(|acc| AccountState {
+                client: acc.client,
+                available: acc.available.amount().serialize(),
+                held: acc.held.amount().serialize(),
+                pending: acc.pending.amount().serialize(),
+                total: acc.total.amount().serialize(),
+                locked: acc.locked,
+                frozen: acc.frozen,
+                label: acc.label.clone(),
+                currency: acc.currency.clone(),
+            })
+            .collect(),
+        transactions: transactions
+            .values()
+            .map(|t| TransactionState {
+                tx_type: t.tx_type.as_ref().map(|ty| ty.to_string()),
+                client: t.client,
+                tx: t.tx,
+                amount: t.amount.map(|a| a.serialize()),
+                timestamp: t
+                    .timestamp
+                    .map(|ts| (ts.timestamp(), ts.timestamp_subsec_nanos())),
+                is_disputed: t.is_disputed,
+                disputed_amount: t.disputed_amount.map(|a| a.serialize()),
+                charged_back: t.charged_back,
+            })
+            .collect(),
+        tx_order: tx_order.iter().copied().collect(),
+    };
+    // Encoding plain structs into memory has no failure case short of a size limit, which the
+    // default options don't set.
+    bincode::serialize(&state).unwrap_or_default()
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<Parts, Box<dyn Error>> {
+    let state: State = bincode::deserialize(bytes)?;
+    let accounts = state
+        .accounts
+        .into_iter()
+        .map(|acc| {
+            let account = ClientAccount {
+                client: acc.client,
+                available: Money::new(Decimal::deserialize(acc.available)),
+                held: Money::new(Decimal::deserialize(acc.held)),
+                pending: Money::new(Decimal::deserialize(acc.pending)),
+                total: Money::new(Decimal::deserialize(acc.total)),
+                locked: acc.locked,
+                frozen: acc.frozen,
+                label: acc.label,
+                currency: acc.currency,
+            };
+            (acc.client, account)
+        })
+        .collect();
+    let mut transactions = HashMap::new();
+    for t in state.transactions {
+        let timestamp = match t.timestamp {
+            Some((secs, nanos)) => match DateTime::from_timestamp(secs, nanos) {
+                Some(ts) => Some(ts),
+                None => return Err(format!("Tx {} has an invalid timestamp", t.tx).into()),
+            },
+            None => None,
+        };
+        let tx = Transaction {
+            tx_type: t.tx_type.map(TxType::from),
+            client: t.client,
+            tx: t.tx,
+            amount: t.amount.map(Decimal::deserialize),
+            timestamp,
+            is_disputed: t.is_disputed,
+            disputed_amount: t.disputed_amount.map(Decimal::deserialize),
+            charged_back: t.charged_back,
+        };
+        transactions.insert(t.tx, tx);
+    }
+    Ok((accounts, transactions, state.tx_order.into_iter().collect()))
+}
@@ -0,0 +1,129 @@
+//! Balance arithmetic which reports overflow as a `ProcessError` instead of panicking.
+
+use crate::money::Money;
+use crate::process_error::ProcessError;
+
+pub fn add_checked(a: impl Into<Money>, b: impl Into<Money>) -> Result<Money, ProcessError> {
+    a.into().checked_add(b.into()).ok_or(ProcessError::Overflow)
+}
+
+pub fn sub_checked(a: impl Into<Money>, b: impl Into<Money>) -> Result<Money, ProcessError> {
+    a.into().checked_sub(b.into()).ok_or(ProcessError::Overflow)
+}
+
+/// An account's total from its parts, i.e. `available + held + pending`.
+pub fn total_checked(
+    available: impl Into<Money>,
+    held: impl Into<Money>,
+    pending: impl Into<Money>,
+) -> Result<Money, ProcessError> {
+    add_checked(add_checked(available, held)?, pending)
+}
+
+/// The sum of the amounts, which is zero for none.
+pub fn sum_checked<M: Into<Money>>(
+    amounts: impl IntoIterator<Item = M>,
+) -> Result<Money, ProcessError> {
+    amounts.into_iter().try_fold(Money::default(), add_checked)
+}
+
+/// Moves `total` by the net `delta` applied to its parts rather than summing them again. Debug
+/// builds check the result against `total_checked`.
+pub fn shift_total(
+    total: impl Into<Money>,
+    delta: impl Into<Money>,
+    available: impl Into<Money>,
+    held: impl Into<Money>,
+    pending: impl Into<Money>,
+) -> Result<Money, ProcessError> {
+    let shifted = add_checked(total, delta)?;
+    debug_assert_eq!(Ok(shifted), total_checked(available, held, pending));
+    Ok(shifted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn add_at_the_decimal_boundaries() {
+        assert_eq!(
+            add_checked(Decimal::new(15, 1), Decimal::new(25, 1)),
+            Ok(Money::new(Decimal::new(4, 0)))
+        );
+        assert_eq!(
+            add_checked(Decimal::MAX, Decimal::new(0, 0)),
+            Ok(Money::new(Decimal::MAX))
+        );
+        assert_eq!(
+            add_checked(Decimal::MAX, Decimal::new(1, 0)),
+            Err(ProcessError::Overflow)
+        );
+        assert_eq!(
+            add_checked(Decimal::MIN, Decimal::new(-1, 0)),
+            Err(ProcessError::Overflow)
+        );
+    }
+
+    #[test]
+    fn sub_at_the_decimal_boundaries() {
+        assert_eq!(
+            sub_checked(Decimal::new(1, 0), Decimal::new(3, 0)),
+            Ok(Money::new(Decimal::new(-2, 0)))
+        );
+        assert_eq!(
+            sub_checked(Decimal::MIN, Decimal::new(1, 0)),
+            Err(ProcessError::Overflow)
+        );
+        assert_eq!(
+            sub_checked(Decimal::MAX, Decimal::new(-1, 0)),
+            Err(ProcessError::Overflow)
+        );
+    }
+
+    #[test]
+    fn total_overflows_when_parts_do() {
+        let zero = Decimal::new(0, 0);
+        assert_eq!(
+            total_checked(Decimal::new(1, 0), Decimal::new(2, 0), Decimal::new(3, 0)),
+            Ok(Money::new(Decimal::new(6, 0)))
+        );
+        assert_eq!(
+            total_checked(Decimal::MAX, zero, Decimal::new(1, 0)),
+            Err(ProcessError::Overflow)
+        );
+    }
+
+    #[test]
+    fn sum_overflows_when_a_partial_sum_does() {
+        assert_eq!(sum_checked(Vec::<Decimal>::new()), Ok(Money::default()));
+        assert_eq!(
+            sum_checked([Decimal::new(1, 0), Decimal::new(25, 1)]),
+            Ok(Money::new(Decimal::new(35, 1)))
+        );
+        assert_eq!(
+            sum_checked([Decimal::MAX, Decimal::MAX]),
+            Err(ProcessError::Overflow)
+        );
+    }
+
+    #[test]
+    fn shift_total_matches_the_recomputed_total() {
+        let zero = Decimal::new(0, 0);
+        assert_eq!(
+            shift_total(
+                Decimal::new(6, 0),
+                Decimal::new(-15, 1),
+                Decimal::new(-5, 1),
+                Decimal::new(2, 0),
+                Decimal::new(3, 0)
+            ),
+            Ok(Money::new(Decimal::new(45, 1)))
+        );
+        assert_eq!(
+            shift_total(Decimal::MAX, Decimal::new(1, 0), Decimal::MAX, zero, zero),
+            Err(ProcessError::Overflow)
+        );
+    }
+}
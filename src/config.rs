@@ -0,0 +1,136 @@
+use chrono::Duration;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Behavioural switches for an `AccountManager`. The defaults match the original spec.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Write every monetary column with exactly `DECIMAL_PRECISION` places, e.g. `1.0000`.
+    pub fixed_scale: bool,
+    /// Treat client `0` as a house account and reject transactions which target it.
+    pub reserve_client_zero: bool,
+    /// Keep every successfully applied transaction, in order, so state can be rebuilt later.
+    pub record_events: bool,
+    /// Whether a row with an unrecognised type is rejected or silently skipped.
+    pub unknown_type_policy: UnknownTypePolicy,
+    /// Bound the transaction store, evicting the oldest undisputed transactions past this size.
+    /// An evicted transaction can no longer be disputed.
+    pub max_tracked_transactions: Option<usize>,
+    /// What to do when a dispute would hold more than the client has available. `Reject` refuses
+    /// disputes of deposits whose funds have since been withdrawn.
+    pub dispute_overhold_policy: DisputeOverholdPolicy,
+    /// Refuse a dispute which would hold more than the client has available, as banks refuse
+    /// disputes on spent funds. Overrides `dispute_overhold_policy` with `Reject`.
+    pub require_funds_for_dispute: bool,
+    /// Record the client's balances after every applied transaction, for debugging.
+    pub trace_balances: bool,
+    /// Check each account's `total` still equals `available + held + pending` after every
+    /// applied transaction, failing with `InvariantViolation` if not. Catches logic bugs early.
+    pub debug_checks: bool,
+    /// The column names used when writing accounts.
+    pub output_schema: OutputSchema,
+    /// Write a negative `available` or `total` as `0`, adding an `overdrawn` column to flag it.
+    pub clamp_negative: bool,
+    /// Report input which the spec doesn't allow for, such as disputes targeting a withdrawal,
+    /// in the `ProcessSummary` as well as rejecting it.
+    pub strict_spec: bool,
+    /// Allow a withdrawal to be disputed: the debit is held pending a resolve, which lets it
+    /// stand, or a chargeback, which refunds it.
+    pub withdrawal_disputes: bool,
+    /// Reject a withdrawal for a client without an account as `NoSuchAccount`. When off, it is
+    /// checked against an empty balance instead, so fails with `InsufficientFunds`.
+    pub withdrawal_requires_account: bool,
+    /// Keep each transaction refused because its account is locked or frozen, for fraud
+    /// monitoring.
+    pub monitor_locked_accounts: bool,
+    /// Decimal places to write for an account in each currency, e.g. `JPY` => 0, `USD` => 2.
+    /// Takes precedence over `fixed_scale` for accounts whose currency is listed.
+    pub currency_precision: HashMap<String, u32>,
+    /// Reject a dispute arriving more than this long after the transaction it names. Both rows
+    /// need a timestamp for the window to apply.
+    pub dispute_window: Option<Duration>,
+    /// Raise a `HeldAlert` whenever a dispute pushes an account's held funds past this amount.
+    pub held_alert_threshold: Option<Decimal>,
+    /// Reject a deposit or withdrawal of more than this amount, as a fraud control.
+    pub max_tx_amount: Option<Decimal>,
+    /// Reject an input amount with more than `DECIMAL_PRECISION` places instead of rounding it.
+    pub strict_precision: bool,
+    /// How amounts in the input separate decimals & thousands.
+    pub decimal_format: DecimalFormat,
+    /// Accept a resolve for a transaction which is not under dispute as a no-op, so a replayed
+    /// resolve doesn't fail.
+    pub idempotent_resolves: bool,
+    /// The field separator of both input and output CSVs, e.g. `b';'` or `b'\t'`.
+    pub delimiter: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            fixed_scale: false,
+            reserve_client_zero: false,
+            record_events: false,
+            unknown_type_policy: UnknownTypePolicy::default(),
+            max_tracked_transactions: None,
+            dispute_overhold_policy: DisputeOverholdPolicy::default(),
+            require_funds_for_dispute: false,
+            trace_balances: false,
+            debug_checks: false,
+            output_schema: OutputSchema::default(),
+            clamp_negative: false,
+            strict_spec: false,
+            withdrawal_disputes: false,
+            withdrawal_requires_account: true,
+            monitor_locked_accounts: false,
+            currency_precision: HashMap::new(),
+            dispute_window: None,
+            held_alert_threshold: None,
+            max_tx_amount: None,
+            strict_precision: false,
+            decimal_format: DecimalFormat::default(),
+            idempotent_resolves: false,
+            delimiter: b',',
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UnknownTypePolicy {
+    #[default]
+    Error,
+    Skip,
+}
+
+/// An amount using a comma, as either separator, must be quoted in the CSV.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DecimalFormat {
+    /// `1,000.50`: a dot for decimals, with optional comma thousands separators.
+    #[default]
+    Dot,
+    /// `1.000,50`: a comma for decimals, with optional dot thousands separators.
+    Comma,
+    /// `150` in minor units, with a `scale` column straight after the amount giving the number
+    /// of decimal places, so `150, 2` is `1.50`. Only CSV input has the extra column.
+    MinorUnits,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DisputeOverholdPolicy {
+    /// Hold the full amount, even if that drives `available` negative.
+    #[default]
+    Allow,
+    /// Hold no more than is currently available.
+    CapAtAvailable,
+    /// Refuse the dispute with `DisputeExceedsAvailable`, e.g. once the deposited funds have
+    /// been withdrawn, as banks refuse disputes on spent funds.
+    Reject,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputSchema {
+    /// `client, available, held, total, locked`
+    #[default]
+    Short,
+    /// `client_id, available_balance, held_balance, total_balance, is_locked, label`
+    Extended,
+}
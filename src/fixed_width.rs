@@ -0,0 +1,111 @@
+//! Parsing of fixed-width transaction records, as exported by some legacy systems.
+
+use crate::process_error::ProcessError;
+use crate::Transaction;
+use crate::TxType;
+use rust_decimal::Decimal;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// The byte range each field occupies on a line. Fields are trimmed of padding, and a field
+/// running past the end of a short line is read up to the end, so a trailing amount is optional.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixedLayout {
+    pub tx_type: Range<usize>,
+    pub client: Range<usize>,
+    pub tx: Range<usize>,
+    pub amount: Range<usize>,
+}
+
+impl FixedLayout {
+    pub fn parse_line(&self, line: &str) -> Result<Transaction, ProcessError> {
+        let tx_type = self.field(line, &self.tx_type)?;
+        if tx_type.is_empty() {
+            return Err(ProcessError::MissingTxType);
+        }
+        let client = self.field(line, &self.client)?;
+        let client = match client.parse::<u16>() {
+            Ok(c) => c,
+            Err(_) if !client.is_empty() && client.bytes().all(|b| b.is_ascii_digit()) => {
+                return Err(ProcessError::ClientIdOutOfRange(client.to_string()))
+            }
+            Err(e) => return Err(malformed("client", e)),
+        };
+        let tx = match self.field(line, &self.tx)?.parse::<u32>() {
+            Ok(t) => t,
+            Err(e) => return Err(malformed("tx", e)),
+        };
+        let amount = match self.field(line, &self.amount)? {
+            "" => None,
+            a => match Decimal::from_str(a) {
+                Ok(a) => Some(a),
+                Err(e) => return Err(malformed("amount", e)),
+            },
+        };
+        Ok(Transaction::new(
+            TxType::from(tx_type.to_string()),
+            client,
+            tx,
+            amount,
+        ))
+    }
+
+    fn field<'a>(&self, line: &'a str, range: &Range<usize>) -> Result<&'a str, ProcessError> {
+        let end = range.end.min(line.len());
+        let start = range.start.min(end);
+        match line.get(start..end) {
+            Some(field) => Ok(field.trim()),
+            None => Err(ProcessError::MalformedRow(format!(
+                "bytes {}..{} split a character",
+                start, end
+            ))),
+        }
+    }
+}
+
+fn malformed(field: &str, e: impl std::fmt::Display) -> ProcessError {
+    ProcessError::MalformedRow(format!("invalid {}: {}", field, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout() -> FixedLayout {
+        FixedLayout {
+            tx_type: 0..10,
+            client: 10..15,
+            tx: 15..25,
+            amount: 25..40,
+        }
+    }
+
+    #[test]
+    fn parses_padded_fields() {
+        let tx = layout()
+            .parse_line("deposit       1         7         12.5000")
+            .unwrap();
+        assert_eq!(tx.tx_type(), Some(&TxType::Deposit));
+        assert_eq!(tx.client(), 1);
+        assert_eq!(tx.tx(), 7);
+        assert_eq!(tx.amount(), Some(Decimal::new(125, 1)));
+
+        let tx = layout().parse_line("dispute       1         7").unwrap();
+        assert_eq!(tx.tx_type(), Some(&TxType::Dispute));
+        assert_eq!(tx.amount(), None);
+    }
+
+    #[test]
+    fn rejects_bad_fields() {
+        assert_eq!(
+            layout()
+                .parse_line("deposit   70000         7")
+                .unwrap_err(),
+            ProcessError::ClientIdOutOfRange("70000".to_string())
+        );
+        assert!(matches!(
+            layout().parse_line("deposit       1       abc"),
+            Err(ProcessError::MalformedRow(_))
+        ));
+    }
+}
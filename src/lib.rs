@@ -1,6 +1,23 @@
+use checked_math::add_checked;
+use checked_math::total_checked;
+use chrono::DateTime;
+use chrono::Utc;
+use money::Money;
+use process_error::ProcessError;
 use rust_decimal::Decimal;
 
 pub mod account_manager;
+#[cfg(feature = "binary")]
+mod binary;
+pub mod checked_math;
+pub mod config;
+pub mod fixed_width;
+pub mod money;
+#[cfg(feature = "parquet")]
+mod parquet_input;
+pub mod process_error;
+pub mod shared_account_manager;
+pub mod tx_lint;
 pub mod tx_processor;
 pub mod tx_stream_reader;
 
@@ -10,38 +27,211 @@ extern crate serde_derive;
 static DECIMAL_PRECISION: u32 = 4;
 
 #[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(from = "String")]
 pub enum TxType {
-    #[serde(alias = "deposit")]
     Deposit,
-    #[serde(alias = "withdraw")]
     Withdraw,
-    #[serde(alias = "dispute")]
     Dispute,
-    #[serde(alias = "resolve")]
     Resolve,
-    #[serde(alias = "chargeback")]
     Chargeback,
+    Pending,
+    Settle,
+    Cancel,
+    Freeze,
+    Unfreeze,
+    Unknown(String),
 }
 
-#[derive(Debug, Serialize)]
+impl From<String> for TxType {
+    /// Type names are matched case-insensitively, so `deposit`, `Deposit` & `DEPOSIT` are equivalent.
+    fn from(value: String) -> Self {
+        match value.to_ascii_lowercase().as_str() {
+            "deposit" => TxType::Deposit,
+            "withdraw" => TxType::Withdraw,
+            "dispute" => TxType::Dispute,
+            "resolve" => TxType::Resolve,
+            "chargeback" => TxType::Chargeback,
+            "pending" => TxType::Pending,
+            "settle" => TxType::Settle,
+            "cancel" => TxType::Cancel,
+            "freeze" => TxType::Freeze,
+            "unfreeze" => TxType::Unfreeze,
+            _ => TxType::Unknown(value),
+        }
+    }
+}
+
+impl std::fmt::Display for TxType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            TxType::Deposit => "deposit",
+            TxType::Withdraw => "withdraw",
+            TxType::Dispute => "dispute",
+            TxType::Resolve => "resolve",
+            TxType::Chargeback => "chargeback",
+            TxType::Pending => "pending",
+            TxType::Settle => "settle",
+            TxType::Cancel => "cancel",
+            TxType::Freeze => "freeze",
+            TxType::Unfreeze => "unfreeze",
+            TxType::Unknown(name) => name,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Accounts order by client id first, as it is the leading field.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct ClientAccount {
     client: u16,
-    available: Decimal,
-    held: Decimal,
-    total: Decimal,
+    available: Money,
+    held: Money,
+    /// Funds withdrawn by a `pending` transaction which has not yet been settled or cancelled.
+    /// They still count towards `total` but are not part of the CSV output.
+    #[serde(skip_serializing)]
+    pending: Money,
+    total: Money,
     locked: bool,
+    /// An administrative hold placed by a `freeze`, which unlike a chargeback lock an `unfreeze`
+    /// can lift. Written as part of the `locked` column.
+    #[serde(skip_serializing)]
+    frozen: bool,
+    /// An operator's grouping for the account, e.g. `vip`, set with `AccountManager::tag`.
+    #[serde(skip_serializing)]
+    label: Option<String>,
+    /// The currency code the account is held in, which picks its output precision from
+    /// `Config::currency_precision`.
+    #[serde(skip_serializing)]
+    currency: Option<String>,
+}
+
+impl ClientAccount {
+    /// An account with nothing pending, whose total is derived from `available` & `held`.
+    /// Fails only if that total overflows.
+    pub fn new(
+        client: u16,
+        available: Decimal,
+        held: Decimal,
+        locked: bool,
+    ) -> Result<Self, ProcessError> {
+        Ok(ClientAccount {
+            client,
+            available: Money::new(available),
+            held: Money::new(held),
+            pending: Money::default(),
+            total: add_checked(available, held)?,
+            locked,
+            frozen: false,
+            label: None,
+            currency: None,
+        })
+    }
+    pub fn client(&self) -> u16 {
+        self.client
+    }
+    pub fn available(&self) -> Money {
+        self.available
+    }
+    pub fn held(&self) -> Money {
+        self.held
+    }
+    pub fn pending(&self) -> Money {
+        self.pending
+    }
+    pub fn total(&self) -> Money {
+        self.total
+    }
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+    pub fn frozen(&self) -> bool {
+        self.frozen
+    }
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+    pub fn currency(&self) -> Option<&str> {
+        self.currency.as_deref()
+    }
+
+    /// The share of the account's total which is held, or `None` while the total is zero.
+    pub fn held_ratio(&self) -> Option<Decimal> {
+        self.held.checked_div(self.total)
+    }
+
+    /// An account is solvent while neither its available funds nor its total have gone negative.
+    pub fn is_solvent(&self) -> bool {
+        !self.available.is_negative() && !self.total.is_negative()
+    }
+
+    /// Fails with `InvariantViolation`, naming the values, unless `total` equals
+    /// `available + held + pending`.
+    pub(crate) fn check_invariant(&self) -> Result<(), ProcessError> {
+        let expected = total_checked(self.available, self.held, self.pending)?;
+        if self.total == expected {
+            return Ok(());
+        }
+        Err(ProcessError::InvariantViolation(format!(
+            "client {} total is {} but available {} + held {} + pending {} is {}",
+            self.client, self.total, self.available, self.held, self.pending, expected
+        )))
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Transaction {
     #[serde(default, alias = "type")]
     tx_type: Option<TxType>,
+    /// Client ids are `u16`; bulk ingestion rejects a wider id rather than letting it wrap.
     #[serde(default)]
     client: u16,
     #[serde(default)]
     tx: u32,
+    /// Dispute, resolve & chargeback rows may leave it empty or omit the column altogether.
     #[serde(default)]
     amount: Option<Decimal>,
+    /// When the transaction happened, as RFC 3339. Only `process_batch` orders by it.
+    #[serde(default)]
+    timestamp: Option<DateTime<Utc>>,
     #[serde(default)]
     is_disputed: bool,
+    #[serde(skip)]
+    disputed_amount: Option<Decimal>,
+    /// Set by a chargeback. `is_disputed` stays set too, so this tells a dispute which was
+    /// charged back from one which is still open.
+    #[serde(skip)]
+    charged_back: bool,
+}
+
+impl Transaction {
+    pub fn new(tx_type: TxType, client: u16, tx: u32, amount: Option<Decimal>) -> Self {
+        Transaction {
+            tx_type: Some(tx_type),
+            client,
+            tx,
+            amount,
+            timestamp: None,
+            is_disputed: false,
+            disputed_amount: None,
+            charged_back: false,
+        }
+    }
+
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn tx_type(&self) -> Option<&TxType> {
+        self.tx_type.as_ref()
+    }
+    pub fn client(&self) -> u16 {
+        self.client
+    }
+    pub fn tx(&self) -> u32 {
+        self.tx
+    }
+    pub fn amount(&self) -> Option<Decimal> {
+        self.amount
+    }
 }
@@ -0,0 +1,121 @@
+//! `Money`, the type of every account balance, so balances can't be mixed up with other decimals
+//! such as ratios. It serializes exactly as the `Decimal` it wraps. A balance may legitimately go
+//! negative, e.g. `available` after a dispute of spent funds, so `Money` can be negative too.
+//! Negation is its only operator, as it cannot overflow; add & subtract with `checked_math`.
+
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Neg;
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Money(Decimal);
+
+impl Money {
+    pub fn new(amount: Decimal) -> Self {
+        Money(amount)
+    }
+
+    pub fn amount(self) -> Decimal {
+        self.0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 < Decimal::new(0, 0)
+    }
+
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        self.0.checked_add(other.0).map(Money)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Option<Money> {
+        self.0.checked_sub(other.0).map(Money)
+    }
+
+    /// This amount as a share of `whole`, or `None` if `whole` is zero.
+    pub fn checked_div(self, whole: Money) -> Option<Decimal> {
+        self.0.checked_div(whole.0)
+    }
+
+    pub fn round_dp(self, dp: u32) -> Money {
+        Money(self.0.round_dp(dp))
+    }
+
+    /// Pads or rounds to exactly `scale` decimal places, e.g. `1.5` to `1.5000`.
+    pub fn rescale(&mut self, scale: u32) {
+        self.0.rescale(scale);
+    }
+}
+
+impl From<Decimal> for Money {
+    fn from(amount: Decimal) -> Self {
+        Money(amount)
+    }
+}
+
+impl From<Money> for Decimal {
+    fn from(money: Money) -> Self {
+        money.0
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl PartialEq<Decimal> for Money {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialOrd<Decimal> for Money {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        self.0.partial_cmp(other)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_matches_decimal() {
+        let a = Money::new(Decimal::new(15, 1));
+        let b = Money::from(Decimal::new(25, 1));
+        assert_eq!(a.checked_add(b), Some(Money::new(Decimal::new(4, 0))));
+        assert_eq!(a.checked_sub(b), Some(Money::new(Decimal::new(-1, 0))));
+        assert_eq!(-a, Decimal::new(-15, 1));
+        assert!(a.checked_sub(b).unwrap().is_negative());
+        assert!(a < Decimal::new(2, 0));
+        assert_eq!(Money::new(Decimal::MAX).checked_add(a), None);
+        assert_eq!(Money::new(Decimal::MIN).checked_sub(a), None);
+    }
+
+    #[test]
+    fn serializes_as_the_decimal() {
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        wtr.serialize((Money::new(Decimal::new(12345, 4)), Decimal::new(12345, 4)))
+            .unwrap();
+        let out = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        assert_eq!(out, "1.2345,1.2345\n");
+
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader("2.50\n".as_bytes());
+        let money: Money = rdr.deserialize().next().unwrap().unwrap();
+        assert_eq!(money, Decimal::new(250, 2));
+    }
+}
@@ -0,0 +1,111 @@
+//! Reading transactions from a Parquet file with `type, client, tx, amount` columns, as
+//! exported by analytics pipelines. Amounts may be stored as text, as numbers or with the
+//! `DECIMAL` logical type.
+
+use crate::process_error::ProcessError;
+use crate::Transaction;
+use crate::TxType;
+use parquet::file::reader::SerializedFileReader;
+use parquet::record::reader::RowIter;
+use parquet::record::{Field, Row};
+use rust_decimal::Decimal;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Every row of the file, read one row group at a time.
+pub(crate) fn rows(path: &Path) -> Result<RowIter<'static>, Box<dyn Error>> {
+    let reader = SerializedFileReader::new(File::open(path)?)?;
+    Ok(reader.into_iter())
+}
+
+pub(crate) fn parse_row(row: &Row) -> Result<Transaction, ProcessError> {
+    let (mut tx_type, mut client, mut tx, mut amount) = (None, None, None, None);
+    for (name, field) in row.get_column_iter() {
+        let slot = match name.as_str() {
+            "type" => &mut tx_type,
+            "client" => &mut client,
+            "tx" => &mut tx,
+            "amount" => &mut amount,
+            _ => continue,
+        };
+        *slot = Some(text(name, field)?);
+    }
+    let tx_type = tx_type.unwrap_or_default();
+    if tx_type.is_empty() {
+        return Err(ProcessError::MissingTxType);
+    }
+    let client = client.unwrap_or_default();
+    let client = match client.parse::<u16>() {
+        Ok(c) => c,
+        Err(_) if !client.is_empty() && client.bytes().all(|b| b.is_ascii_digit()) => {
+            return Err(ProcessError::ClientIdOutOfRange(client))
+        }
+        Err(e) => return Err(malformed("client", e)),
+    };
+    let tx = match tx.unwrap_or_default().parse::<u32>() {
+        Ok(t) => t,
+        Err(e) => return Err(malformed("tx", e)),
+    };
+    let amount = match amount.as_deref() {
+        None | Some("") => None,
+        Some(a) => match Decimal::from_str(a) {
+            Ok(a) => Some(a),
+            Err(e) => return Err(malformed("amount", e)),
+        },
+    };
+    Ok(Transaction::new(TxType::from(tx_type), client, tx, amount))
+}
+
+/// A column's value as the text it would have in a CSV, with a null left empty.
+fn text(name: &str, field: &Field) -> Result<String, ProcessError> {
+    let text = match field {
+        Field::Null => String::new(),
+        Field::Str(s) => s.trim().to_string(),
+        Field::Byte(n) => n.to_string(),
+        Field::Short(n) => n.to_string(),
+        Field::Int(n) => n.to_string(),
+        Field::Long(n) => n.to_string(),
+        Field::UByte(n) => n.to_string(),
+        Field::UShort(n) => n.to_string(),
+        Field::UInt(n) => n.to_string(),
+        Field::ULong(n) => n.to_string(),
+        Field::Float(n) => n.to_string(),
+        Field::Double(n) => n.to_string(),
+        Field::Decimal(d) => decimal_text(name, d)?,
+        _ => {
+            return Err(ProcessError::MalformedRow(format!(
+                "unsupported {} column type",
+                name
+            )))
+        }
+    };
+    Ok(text)
+}
+
+/// A `DECIMAL` column's value, stored as a big-endian two's complement unscaled integer.
+fn decimal_text(name: &str, d: &parquet::data_type::Decimal) -> Result<String, ProcessError> {
+    let bytes = d.data();
+    if bytes.len() > 16 || d.scale() < 0 {
+        return Err(ProcessError::MalformedRow(format!(
+            "unsupported {} decimal",
+            name
+        )));
+    }
+    let sign = if bytes.first().is_some_and(|b| *b >= 0x80) {
+        -1
+    } else {
+        0
+    };
+    let unscaled = bytes
+        .iter()
+        .fold(sign, |acc: i128, b| (acc << 8) | i128::from(*b));
+    Decimal::try_from_i128_with_scale(unscaled, d.scale() as u32)
+        .map(|d| d.to_string())
+        .map_err(|e| malformed(name, e))
+}
+
+fn malformed(field: &str, e: impl std::fmt::Display) -> ProcessError {
+    ProcessError::MalformedRow(format!("invalid {}: {}", field, e))
+}
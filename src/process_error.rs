@@ -0,0 +1,103 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProcessError {
+    MissingTxType,
+    UnknownTxType(String),
+    ReservedClient,
+    AmountRequired,
+    NegativeAmount,
+    ZeroAmount,
+    TooPrecise(u32),
+    AmountTooLarge,
+    DuplicateTransaction,
+    AccountLocked,
+    AccountFrozen,
+    InsufficientFunds,
+    NoSuchAccount,
+    AccountMissingForTransaction,
+    TransactionNotFound,
+    ClientMismatch,
+    NotDisputable,
+    AlreadyDisputed,
+    DisputeExceedsOriginal,
+    DisputeExceedsAvailable,
+    NotDisputed,
+    DisputeWindowExpired,
+    HeldUnderflow,
+    Overflow,
+    NotPending,
+    NotReassignable,
+    BalanceNotZero,
+    DisputesOpen,
+    PendingOpen,
+    FilteredOut,
+    InvariantViolation(String),
+    MalformedRow(String),
+    ClientIdOutOfRange(String),
+    InvalidClientId(String),
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProcessError::MissingTxType => write!(f, "No Tx Type provided"),
+            ProcessError::UnknownTxType(name) => write!(f, "Unknown Tx Type: {}", name),
+            ProcessError::ReservedClient => write!(f, "Client Id is Reserved"),
+            ProcessError::AmountRequired => write!(f, "Amount Required"),
+            ProcessError::NegativeAmount => write!(f, "Cannot Process a Negative Amount"),
+            ProcessError::ZeroAmount => write!(f, "Cannot Dispute a Zero Amount"),
+            ProcessError::AmountTooLarge => write!(f, "Amount exceeds the Transaction Limit"),
+            ProcessError::TooPrecise(places) => {
+                write!(f, "Amount has more than {} Decimal Places", places)
+            }
+            ProcessError::DuplicateTransaction => write!(f, "Duplicate Transaction"),
+            ProcessError::AccountLocked => write!(f, "Account Locked due to Chargeback"),
+            ProcessError::AccountFrozen => write!(f, "Account Frozen by an Administrative Hold"),
+            ProcessError::InsufficientFunds => write!(f, "Insufficient Funds"),
+            ProcessError::NoSuchAccount => write!(f, "No Associated Client Account Found"),
+            ProcessError::AccountMissingForTransaction => {
+                write!(f, "Transaction exists but its Client Account is missing")
+            }
+            ProcessError::TransactionNotFound => {
+                write!(f, "No Associated Transaction could be Found")
+            }
+            ProcessError::ClientMismatch => {
+                write!(f, "Transaction belongs to a different Client")
+            }
+            ProcessError::NotDisputable => write!(f, "Only a Deposit can be disputed"),
+            ProcessError::AlreadyDisputed => write!(f, "Transaction is already disputed"),
+            ProcessError::DisputeExceedsOriginal => {
+                write!(f, "Disputed Amount exceeds the Original Transaction")
+            }
+            ProcessError::DisputeExceedsAvailable => {
+                write!(f, "Disputed Amount exceeds the Available Funds")
+            }
+            ProcessError::NotDisputed => write!(f, "Transaction is not disputed"),
+            ProcessError::DisputeWindowExpired => {
+                write!(f, "Transaction is too old to be disputed")
+            }
+            ProcessError::HeldUnderflow => {
+                write!(f, "Held Funds are less than the Disputed Amount")
+            }
+            ProcessError::Overflow => write!(f, "Balance is outside the representable range"),
+            ProcessError::NotPending => write!(f, "Transaction is not a pending withdrawal"),
+            ProcessError::BalanceNotZero => write!(f, "Account still holds Funds"),
+            ProcessError::DisputesOpen => write!(f, "Account has Open Disputes"),
+            ProcessError::PendingOpen => write!(f, "Account has Pending Withdrawals"),
+            ProcessError::NotReassignable => write!(f, "Only a Deposit can be reassigned"),
+            ProcessError::FilteredOut => write!(f, "Rejected by the Transaction Filter"),
+            ProcessError::InvariantViolation(detail) => {
+                write!(f, "Balance Invariant Violated: {}", detail)
+            }
+            ProcessError::MalformedRow(reason) => write!(f, "Malformed Row: {}", reason),
+            ProcessError::ClientIdOutOfRange(raw) => {
+                write!(f, "Client Id {} is outside the range 0-65535", raw)
+            }
+            ProcessError::InvalidClientId(raw) => write!(f, "Invalid Client Id: '{}'", raw),
+        }
+    }
+}
+
+impl Error for ProcessError {}
@@ -0,0 +1,74 @@
+use crate::account_manager::AccountManager;
+use crate::process_error::ProcessError;
+use crate::ClientAccount;
+use crate::Transaction;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A thread-safe handle onto an `AccountManager`. Clones share the same underlying state.
+#[derive(Clone, Default)]
+pub struct SharedAccountManager(Arc<Mutex<AccountManager>>);
+
+impl SharedAccountManager {
+    pub fn new(acc_man: AccountManager) -> Self {
+        SharedAccountManager(Arc::new(Mutex::new(acc_man)))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, AccountManager> {
+        // Keep serving after another thread panicked while holding the lock. Nothing is rolled
+        // back, so an account that thread was part-way through updating stays as it left it.
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn process_tx(&self, tx: &Transaction) -> Result<(), ProcessError> {
+        self.lock().process_tx(tx)
+    }
+
+    pub fn balance(&self, client: u16) -> Option<ClientAccount> {
+        self.lock().accounts.get(&client).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TxType;
+    use rust_decimal::Decimal;
+    use std::thread;
+
+    #[test]
+    fn concurrent_deposits_to_distinct_clients() {
+        let shared = SharedAccountManager::default();
+        let handles: Vec<_> = (1u16..=4)
+            .map(|client_id| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for i in 0..10u32 {
+                        let tx = Transaction {
+                            tx_type: Some(TxType::Deposit),
+                            client: client_id,
+                            tx: u32::from(client_id) * 100 + i,
+                            amount: Some(Decimal::new(1, 0)),
+                            timestamp: None,
+                            is_disputed: false,
+                            disputed_amount: None,
+                            charged_back: false,
+                        };
+                        assert!(shared.process_tx(&tx).is_ok());
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        for client_id in 1u16..=4 {
+            let account = shared.balance(client_id).unwrap();
+            assert_eq!(account.available, Decimal::new(10, 0));
+            assert_eq!(account.total, Decimal::new(10, 0));
+        }
+        assert!(shared.balance(5u16).is_none());
+    }
+}
@@ -0,0 +1,107 @@
+//! A pre-flight check over a batch of transactions which reports suspicious rows without
+//! applying anything. `index` is the transaction's position in the batch.
+
+use crate::Transaction;
+use crate::TxType;
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LintWarning {
+    /// A dispute, resolve, chargeback, settle or cancel whose transaction only appears later on.
+    ForwardReference { index: usize, tx: u32 },
+    /// A deposit, withdrawal or pending withdrawal reusing an earlier transaction's id.
+    DuplicateTxId { index: usize, tx: u32 },
+    /// A dispute, resolve or chargeback carrying an amount, which the spec doesn't expect.
+    AmountOnDisputeRow { index: usize, tx: u32 },
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LintWarning::ForwardReference { index, tx } => {
+                write!(f, "#{}: Tx {} is referenced before it occurs", index, tx)
+            }
+            LintWarning::DuplicateTxId { index, tx } => {
+                write!(f, "#{}: Tx {} is a duplicate id", index, tx)
+            }
+            LintWarning::AmountOnDisputeRow { index, tx } => {
+                write!(f, "#{}: Tx {} has an amount on a dispute row", index, tx)
+            }
+        }
+    }
+}
+
+pub fn lint_stream(txns: &[Transaction]) -> Vec<LintWarning> {
+    let originates = |t: &Transaction| {
+        matches!(
+            t.tx_type,
+            Some(TxType::Deposit) | Some(TxType::Withdraw) | Some(TxType::Pending)
+        )
+    };
+    let all_ids: HashSet<u32> = txns
+        .iter()
+        .filter(|t| originates(t))
+        .map(|t| t.tx)
+        .collect();
+    let mut seen = HashSet::new();
+    let mut warnings = Vec::new();
+    for (index, t) in txns.iter().enumerate() {
+        if originates(t) {
+            if !seen.insert(t.tx) {
+                warnings.push(LintWarning::DuplicateTxId { index, tx: t.tx });
+            }
+            continue;
+        }
+        let (references, dispute_row) = match t.tx_type {
+            Some(TxType::Dispute) | Some(TxType::Resolve) | Some(TxType::Chargeback) => {
+                (true, true)
+            }
+            Some(TxType::Settle) | Some(TxType::Cancel) => (true, false),
+            _ => (false, false),
+        };
+        if references && !seen.contains(&t.tx) && all_ids.contains(&t.tx) {
+            warnings.push(LintWarning::ForwardReference { index, tx: t.tx });
+        }
+        if dispute_row && t.amount.is_some() {
+            warnings.push(LintWarning::AmountOnDisputeRow { index, tx: t.tx });
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn dispute_before_its_deposit_is_flagged() {
+        let txns = vec![
+            Transaction::new(TxType::Dispute, 1, 2, None),
+            Transaction::new(TxType::Deposit, 1, 1, Some(Decimal::new(5, 0))),
+            Transaction::new(TxType::Deposit, 1, 2, Some(Decimal::new(3, 0))),
+            Transaction::new(TxType::Dispute, 1, 1, None),
+        ];
+        assert_eq!(
+            lint_stream(&txns),
+            vec![LintWarning::ForwardReference { index: 0, tx: 2 }]
+        );
+    }
+
+    #[test]
+    fn duplicate_ids_and_dispute_amounts_are_flagged() {
+        let txns = vec![
+            Transaction::new(TxType::Deposit, 1, 1, Some(Decimal::new(5, 0))),
+            Transaction::new(TxType::Withdraw, 1, 1, Some(Decimal::new(1, 0))),
+            Transaction::new(TxType::Dispute, 1, 1, Some(Decimal::new(2, 0))),
+        ];
+        assert_eq!(
+            lint_stream(&txns),
+            vec![
+                LintWarning::DuplicateTxId { index: 1, tx: 1 },
+                LintWarning::AmountOnDisputeRow { index: 2, tx: 1 },
+            ]
+        );
+    }
+}
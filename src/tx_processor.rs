@@ -1,7 +1,5 @@
 use crate::account_manager::AccountManager;
 use crate::tx_stream_reader::TxStreamReader;
-use crate::Transaction;
-use crate::DECIMAL_PRECISION;
 
 pub struct TxProcessor {
     tx_stream: TxStreamReader,
@@ -14,25 +12,11 @@ impl TxProcessor {
     }
 
     pub async fn start(&mut self) {
-        for buf in self.tx_stream.stream.records() {
-            match buf {
-                Ok(tx) => {
-                    match tx.deserialize::<Transaction>(None) {
-                        Ok(mut deserialized_tx) => {
-                            deserialized_tx.amount = match deserialized_tx.amount {
-                                Some(a) => Some(a.round_dp(DECIMAL_PRECISION)),
-                                None => None,
-                            };
-                            match self.acc_man.process_tx(&deserialized_tx) {
-                                Ok(_) => {}
-                                Err(e) => eprintln!("Error: {} : {:?}", e, tx),
-                            };
-                        }
-                        Err(e) => eprintln!("Error: {} : {:?}", e, tx),
-                    };
-                }
-                Err(e) => eprintln!("Could not read line: {}", e),
-            }
+        if let Err(e) = self.acc_man.process_stream(&mut self.tx_stream) {
+            eprintln!("Could not read line: {}", e);
+        }
+        for rejection in self.acc_man.rejections() {
+            eprintln!("Error: {}", rejection);
         }
     }
 
@@ -1,23 +1,34 @@
 use std::error::Error;
 use std::fs::File;
+use std::io;
 use std::io::BufReader;
 
-pub struct TxStreamReader {
-    pub stream: csv::Reader<BufReader<File>>,
+pub struct TxStreamReader<R = BufReader<File>> {
+    pub stream: csv::Reader<R>,
 }
 
-impl TxStreamReader {
+impl TxStreamReader<BufReader<File>> {
     pub fn new_from_csv(csv_path: String) -> Result<Self, Box<dyn Error>> {
         let file = File::open(csv_path)?;
         let buffered_file_reader = BufReader::new(file);
-        let tsr: csv::Reader<BufReader<File>> = TxStreamReader::csv_reader(buffered_file_reader);
-        Ok(TxStreamReader { stream: tsr })
+        Ok(TxStreamReader::new_from_reader(buffered_file_reader))
     }
-    fn csv_reader(reader: BufReader<File>) -> csv::Reader<BufReader<File>> {
+}
+
+impl<R: io::Read> TxStreamReader<R> {
+    pub fn new_from_reader(reader: R) -> Self {
+        TxStreamReader::new_from_reader_with_delimiter(reader, b',')
+    }
+    /// For input separated by something other than a comma, e.g. `b';'` or `b'\t'`.
+    pub fn new_from_reader_with_delimiter(reader: R, delimiter: u8) -> Self {
+        let tsr: csv::Reader<R> = TxStreamReader::csv_reader(reader, delimiter);
+        TxStreamReader { stream: tsr }
+    }
+    fn csv_reader(reader: R, delimiter: u8) -> csv::Reader<R> {
         let csv_reader = csv::ReaderBuilder::new()
             .trim(csv::Trim::All)
             .has_headers(true)
-            .delimiter(b',')
+            .delimiter(delimiter)
             .flexible(true)
             .double_quote(false)
             .from_reader(reader);